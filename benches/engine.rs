@@ -0,0 +1,33 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::{hint::black_box, sync::Arc};
+use wise_key::event::{run_uncached, set_backend, Data, Iteration, MockBackend};
+
+///a recording of the usual shape, moves, clicks and keys without waits so only the engine is timed
+fn recording() -> Data {
+    let mut content = String::new();
+    for step in 0..50 {
+        content += &format!("mouse_move({},{});\n", step * 10, step * 5);
+        content += &format!("button_left_press({},{});\n", step * 10, step * 5);
+        content += &format!("button_left_release({},{});\n", step * 10, step * 5);
+        content += "key_press(Key::a);\nkey_release(Key::a);\n";
+    }
+    Data::new("bench".to_string(), content)
+}
+
+fn engine(c: &mut Criterion) {
+    set_backend(Arc::new(MockBackend::new()));
+    let data = recording();
+    let iteration = Iteration { index: 0, count: 1 };
+    let mut group = c.benchmark_group("loop");
+    //a new engine, scope and compile on every loop, as before the thread local cache
+    group.bench_function("fresh engine and compile", |b| {
+        b.iter(|| black_box(run_uncached(&data.content, &iteration).is_ok()))
+    });
+    group.bench_function("cached engine and ast", |b| {
+        b.iter(|| black_box(data.simulate(&iteration).is_ok()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, engine);
+criterion_main!(benches);
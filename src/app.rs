@@ -2,85 +2,224 @@ use crate::capture;
 #[allow(unused_imports)]
 use crate::i;
 use crate::{
-    common::{clean_instant, init_instant, simulate_state_send, Int},
-    event::{events_to_data, grab, Data, Event, Key},
+    common::{
+        clean_instant, init_instant, set_speed, simulate_state_send, Float, Int, SimulateSignal,
+        UInt,
+    },
+    database::{Database, DATABASE_PATH},
+    event::{
+        abort, active_time, begin_report, events_to_data, export_file, finish_report,
+        forget_display_size, grab, import_file, in_corner, is_running, is_synthetic, release_held,
+        report_panic, report_result, reports, reset_control, running, set_event_limit,
+        set_legacy_keys, sleep_unless_stopped, stop_requested, Data, EscapeCounter, Event,
+        Iteration, Key, KeyState, Modifiers, Report, Running, SimulateError,
+    },
+    random::{new_seed, set_jitter, set_seed},
+    settings::{Binding, LoopTimes, Settings, Speed, Trigger},
 };
 use eframe::CreationContext;
 #[allow(unused_imports)]
 use log::{info, warn};
 use std::{
-    sync::{Arc, RwLock},
-    thread::{spawn, JoinHandle},
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    thread::{sleep, spawn, JoinHandle},
+    time::Duration,
 };
 
+///how often settings edited outside the app are looked for
+const SETTINGS_POLL: Duration = Duration::from_secs(1);
+///how often the watchdog checks the runtime of a run
+const WATCHDOG_POLL: Duration = Duration::from_millis(100);
+
 pub struct App {
-    hotkey: Arc<RwLock<HotKey>>,
-    loop_times: Arc<RwLock<LoopTimes>>,
-    mouse_filter: Arc<RwLock<bool>>,
+    ///everything the user may change, shared with the grab and settings threads
+    settings: Arc<RwLock<Settings>>,
     events: Arc<RwLock<Vec<Event>>>,
     data: Arc<RwLock<Option<Data>>>,
+    database: Arc<Mutex<Option<Database>>>,
     state: Arc<RwLock<State>>,
     grab_handle: Option<JoinHandle<()>>,
+    settings_handle: Option<JoinHandle<()>>,
     capture: capture::Capture,
+    ///file a macro is exported to or imported from, json or ron by its extension
+    exchange_path: String,
+    ///outcome of the last library action, shown under the library
+    notice: Option<String>,
+    ///stored macros as last listed, the most recently updated first
+    library: Vec<Data>,
+    ///name typed for the selected macro
+    new_name: String,
+    ///settings as edited, json of every field
+    settings_text: String,
+    ///outcome of the last settings change
+    settings_notice: Option<String>,
 }
 
 impl App {
     pub fn new(cc: &CreationContext) -> Self {
+        let database = Self::open_database();
+        let settings = Self::load_settings(&database).unwrap_or_default();
+        let settings = Arc::new(RwLock::new(settings));
         let mut app = Self {
-            hotkey: Arc::new(RwLock::new(HotKey::default())),
-            mouse_filter: Arc::new(RwLock::new(false)),
-            loop_times: Arc::new(RwLock::new(LoopTimes::Limited(1))),
+            settings: Arc::clone(&settings),
             state: Arc::new(RwLock::new(State::default())),
             events: Arc::new(RwLock::new(Vec::new())),
             data: Arc::new(RwLock::new(None)),
+            database: Arc::new(Mutex::new(database)),
             grab_handle: None,
-            capture: capture::Capture::new(cc),
+            settings_handle: None,
+            capture: capture::Capture::new(cc, settings),
+            exchange_path: "macro.json".to_string(),
+            notice: None,
+            library: Vec::new(),
+            new_name: String::new(),
+            settings_text: String::new(),
+            settings_notice: None,
         };
-        let hotkey = Arc::clone(&app.hotkey);
-        let mouse_filter = Arc::clone(&app.mouse_filter);
-        let loop_times = Arc::clone(&app.loop_times);
+        app.refresh();
+        app.settings_text = app.settings_json();
+        let database = Arc::clone(&app.database);
+        let settings = Arc::clone(&app.settings);
+        app.settings_handle = Some(spawn(move || Self::_watch_settings(database, settings)));
+        let settings = Arc::clone(&app.settings);
         let state = Arc::clone(&app.state);
         let events = Arc::clone(&app.events);
         let data = Arc::clone(&app.data);
+        let database = Arc::clone(&app.database);
         app.grab_handle = Some(spawn(move || {
-            Self::_grab(hotkey, mouse_filter, loop_times, state, events, data)
+            Self::_grab(settings, state, events, data, database)
         }));
         app
     }
+    fn open_database() -> Option<Database> {
+        match Database::open(DATABASE_PATH) {
+            Ok(database) => Some(database),
+            Err(err) => {
+                warn!("open database error: {:?}", err);
+                None
+            }
+        }
+    }
+    fn load_settings(database: &Option<Database>) -> Option<Settings> {
+        match Settings::load(database.as_ref()?) {
+            Ok(settings) => Some(settings),
+            Err(err) => {
+                warn!("load settings error: {:?}", err);
+                None
+            }
+        }
+    }
+    ///reload settings whenever the database is changed by someone else
+    fn _watch_settings(database: Arc<Mutex<Option<Database>>>, settings: Arc<RwLock<Settings>>) {
+        let mut version = None;
+        loop {
+            sleep(SETTINGS_POLL);
+            let guard = database.lock().unwrap();
+            let database = match *guard {
+                Some(ref database) => database,
+                None => return,
+            };
+            let current = match database.data_version() {
+                Ok(current) => current,
+                Err(err) => {
+                    warn!("settings version error: {:?}", err);
+                    continue;
+                }
+            };
+            //the first sample only sets the baseline
+            let previous = version.replace(current);
+            if previous.is_none() || previous == Some(current) {
+                continue;
+            }
+            let reloaded = match Settings::load(database) {
+                Ok(reloaded) => reloaded,
+                Err(err) => {
+                    warn!("reload settings error: {:?}", err);
+                    continue;
+                }
+            };
+            //stop holds settings while it saves, never wait for them holding the database
+            drop(guard);
+            info!("settings reloaded");
+            *settings.write().unwrap() = reloaded;
+        }
+    }
     fn _grab(
-        hotkey: Arc<RwLock<HotKey>>,
-        mouse_filter: Arc<RwLock<bool>>,
-        loop_times: Arc<RwLock<LoopTimes>>,
+        settings: Arc<RwLock<Settings>>,
         state: Arc<RwLock<State>>,
         events: Arc<RwLock<Vec<Event>>>,
         data: Arc<RwLock<Option<Data>>>,
+        database: Arc<Mutex<Option<Database>>>,
     ) {
         let state_clone = Arc::clone(&state);
+        let keys = Arc::new(Mutex::new(KeyState::default()));
+        let escapes = Arc::new(Mutex::new(EscapeCounter::default()));
         if let Err(_) = grab(move |_event| {
-            let hotkey = Arc::clone(&hotkey);
-            let mouse_filter = Arc::clone(&mouse_filter);
-            let loop_times = Arc::clone(&loop_times);
+            let settings = Arc::clone(&settings);
             let state = Arc::clone(&state);
             let events_stop = Arc::clone(&events);
             let events_push = Arc::clone(&events);
             let data = Arc::clone(&data);
+            let database = Arc::clone(&database);
             let event: Event = _event.clone().into();
+            //our own playback goes on to the OS, but is never recorded nor taken as a hotkey
+            if is_synthetic(&event) {
+                return Some(_event);
+            }
+            Self::check_failsafe(&state, &settings, &escapes, &event);
+            let locked = Self::is_locked(&state, &settings);
             match event {
-                Event::KeyPress { key, .. } if hotkey.read().unwrap().contains(&key) => None,
-                Event::KeyRelease { key, .. } if key == hotkey.read().unwrap().record => {
-                    Self::record(state);
-                    None
-                }
-                Event::KeyRelease { key, .. } if key == hotkey.read().unwrap().simulate => {
-                    Self::simulate(state, loop_times, data);
-                    None
+                Event::KeyPress { key, .. } => {
+                    let modifiers = keys.lock().unwrap().press(key);
+                    match Self::action(&settings, key, modifiers) {
+                        Some((Action::Pause | Action::Stop, trigger)) if trigger.passthrough => {
+                            Some(_event)
+                        }
+                        Some((Action::Pause | Action::Stop, _)) => None,
+                        _ if locked => None,
+                        Some((_, trigger)) if trigger.passthrough => Some(_event),
+                        Some(_) => None,
+                        None => {
+                            Self::_push(state, &settings, event, events_push);
+                            Some(_event)
+                        }
+                    }
                 }
-                Event::KeyRelease { key, .. } if key == hotkey.read().unwrap().stop => {
-                    Self::stop(state, events_stop, data);
-                    None
+                Event::KeyRelease { key, .. } => {
+                    let modifiers = keys.lock().unwrap().release(key);
+                    let (action, trigger) = match Self::action(&settings, key, modifiers) {
+                        Some(found @ (Action::Pause | Action::Stop, _)) => found,
+                        //let releases through, a key held before the lock must not stay down
+                        _ if locked => return Some(_event),
+                        Some(found) => found,
+                        None => {
+                            Self::_push(state, &settings, event, events_push);
+                            return Some(_event);
+                        }
+                    };
+                    match action {
+                        Action::Record => Self::record(state),
+                        Action::Simulate => Self::simulate(state, settings, data, database),
+                        Action::Pause => Self::pause(state),
+                        Action::Stop => Self::stop(state, events_stop, settings, data, database),
+                        Action::Run(binding) => {
+                            Self::simulate_binding(state, binding, settings, database)
+                        }
+                    }
+                    if trigger.passthrough {
+                        Some(_event)
+                    } else {
+                        None
+                    }
                 }
+                Event::ButtonRelease { .. } if locked => Some(_event),
+                _ if locked => None,
                 _ => {
-                    Self::_push(state, mouse_filter, event, events_push);
+                    Self::_push(state, &settings, event, events_push);
                     Some(_event)
                 }
             }
@@ -101,7 +240,9 @@ impl App {
     fn stop(
         state: Arc<RwLock<State>>,
         events: Arc<RwLock<Vec<Event>>>,
+        settings: Arc<RwLock<Settings>>,
         data: Arc<RwLock<Option<Data>>>,
+        database: Arc<Mutex<Option<Database>>>,
     ) {
         info!("stopping");
         if *state.read().unwrap() == State::Stop {
@@ -113,11 +254,16 @@ impl App {
         let len = events.read().unwrap().len().clone();
         match (previous_state, len > 0) {
             (State::Record, true) => {
-                *data.write().unwrap() = Some(events_to_data(&*events.read().unwrap()));
+                let (fidelity, simplify) = {
+                    let settings = settings.read().unwrap();
+                    (settings.fidelity, settings.simplify)
+                };
+                let recorded = events_to_data(&events.read().unwrap(), fidelity, &simplify);
+                *data.write().unwrap() = Some(Self::save(&database, recorded));
                 *events.write().unwrap() = Vec::new();
             }
-            (State::Simulate, _) => {
-                simulate_state_send(true);
+            (State::Simulate, _) | (State::Paused, _) => {
+                simulate_state_send(SimulateSignal::Stop);
             }
             (_, _) => {}
         };
@@ -127,57 +273,537 @@ impl App {
         }
     }
 
+    ///toggle between simulating and paused, held keys stay pressed while paused
+    fn pause(state: Arc<RwLock<State>>) {
+        let current = *state.read().unwrap();
+        match current {
+            State::Simulate => {
+                info!("pausing");
+                *state.write().unwrap() = State::Paused;
+                simulate_state_send(SimulateSignal::Pause);
+            }
+            State::Paused => {
+                info!("resuming");
+                *state.write().unwrap() = State::Simulate;
+                simulate_state_send(SimulateSignal::Resume);
+            }
+            _ => {}
+        }
+    }
+
     fn simulate(
         state: Arc<RwLock<State>>,
-        loop_times: Arc<RwLock<LoopTimes>>,
+        settings: Arc<RwLock<Settings>>,
         data: Arc<RwLock<Option<Data>>>,
+        database: Arc<Mutex<Option<Database>>>,
     ) {
         info!("simulating");
-        if *state.read().unwrap() != State::Stop {
-            return;
+        let running = match Self::start(&state) {
+            Some(running) => running,
+            None => return,
+        };
+        spawn(move || {
+            Self::load(&database, &data);
+            let loaded = data.read().unwrap().clone();
+            let settings = settings.read().unwrap().clone();
+            match loaded {
+                Some(data) => Self::run(
+                    state,
+                    running,
+                    data,
+                    settings.loop_times.clone(),
+                    settings.speed,
+                    &settings,
+                ),
+                None => *state.write().unwrap() = State::Stop,
+            }
+        });
+    }
+    ///what key pressed with modifiers triggers, hotkeys win over bindings
+    fn action(
+        settings: &Arc<RwLock<Settings>>,
+        key: Key,
+        modifiers: Modifiers,
+    ) -> Option<(Action, Trigger)> {
+        let settings = settings.read().unwrap();
+        let hotkey = settings.hotkey;
+        let actions = [
+            (Action::Record, hotkey.record),
+            (Action::Simulate, hotkey.simulate),
+            (Action::Pause, hotkey.pause),
+            (Action::Stop, hotkey.stop),
+        ];
+        if let Some(found) = actions
+            .into_iter()
+            .find(|(_, trigger)| trigger.chord.matches(key, modifiers))
+        {
+            return Some(found);
+        }
+        settings
+            .bindings
+            .iter()
+            .find(|binding| binding.trigger.chord.matches(key, modifiers))
+            .map(|binding| (Action::Run(binding.clone()), binding.trigger))
+    }
+    ///run the library macro of binding with its own loop and speed settings
+    fn simulate_binding(
+        state: Arc<RwLock<State>>,
+        binding: Binding,
+        settings: Arc<RwLock<Settings>>,
+        database: Arc<Mutex<Option<Database>>>,
+    ) {
+        info!("simulating {}", binding.name);
+        let running = match Self::start(&state) {
+            Some(running) => running,
+            None => return,
+        };
+        spawn(move || {
+            let loaded = match *database.lock().unwrap() {
+                Some(ref database) => database.read_by_name(&binding.name),
+                None => Ok(None),
+            };
+            let settings = settings.read().unwrap().clone();
+            match loaded {
+                Ok(Some(data)) => Self::run(
+                    state,
+                    running,
+                    data,
+                    binding.loop_times,
+                    binding.speed,
+                    &settings,
+                ),
+                Ok(None) => {
+                    warn!("no macro named {}", binding.name);
+                    *state.write().unwrap() = State::Stop;
+                }
+                Err(err) => {
+                    warn!("load {} error: {:?}", binding.name, err);
+                    *state.write().unwrap() = State::Stop;
+                }
+            }
+        });
+    }
+    ///switch to Simulate right on the hotkey so a stop sent while data loads is kept,
+    ///None while a run is still going or has not finished stopping
+    fn start(state: &Arc<RwLock<State>>) -> Option<Running> {
+        let mut state = state.write().unwrap();
+        if *state != State::Stop || is_running() {
+            return None;
         }
+        //signals left by the previous run go before any stop meant for this one
+        reset_control();
+        forget_display_size();
+        *state = State::Simulate;
+        Some(running())
+    }
+    ///loop data on its own thread, state goes back to Stop once done,
+    ///loop_times and speed are given apart as a binding brings its own
+    fn run(
+        state: Arc<RwLock<State>>,
+        running: Running,
+        data: Data,
+        loop_times: LoopTimes,
+        speed: Speed,
+        settings: &Settings,
+    ) {
+        let (variation, failsafe) = (settings.variation, settings.failsafe);
+        set_speed(speed.factor(&data));
+        let seed = variation.seed.unwrap_or_else(new_seed);
+        set_seed(seed);
+        set_jitter(variation.jitter);
+        set_event_limit(failsafe.max_events_per_second);
+        set_legacy_keys(settings.legacy_keys);
+        let interval = Duration::from_millis(settings.loop_interval as u64);
+        //在一个线程的话无法继续监听hotkey
+        spawn(move || {
+            let _running = running;
+            begin_report(&data.name, seed);
+            let finished = Arc::new(AtomicBool::new(false));
+            if failsafe.max_runtime > 0 {
+                Self::_watchdog(
+                    Arc::clone(&finished),
+                    Duration::from_secs(failsafe.max_runtime as u64),
+                );
+            }
+            let mut iteration: UInt = 0;
+            let result = catch_unwind(AssertUnwindSafe(|| loop {
+                if *state.read().unwrap() == State::Stop || stop_requested() {
+                    report_result(iteration, &Err(SimulateError::Stopped));
+                    break;
+                }
+                let current = Iteration {
+                    index: iteration as Int,
+                    count: loop_times.count(),
+                };
+                if iteration > 0 && !sleep_unless_stopped(interval) {
+                    report_result(iteration, &Err(SimulateError::Stopped));
+                    break;
+                }
+                match loop_times.next(&current, active_time()) {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(err) => {
+                        report_result(iteration + 1, &Err(err));
+                        break;
+                    }
+                }
+                iteration += 1;
+                let result = data.simulate(&current);
+                report_result(iteration, &result);
+                if result.is_err() {
+                    break;
+                }
+            }));
+            if result.is_err() {
+                warn!("simulate thread panicked");
+                report_panic(iteration);
+            }
+            //stop, never leave a modifier or button pressed
+            release_held();
+            set_speed(1 as Float);
+            set_jitter(0 as Float);
+            set_event_limit(0);
+            finished.store(true, Ordering::SeqCst);
+            finish_report();
+            *state.write().unwrap() = State::Stop;
+        });
+    }
+    ///abort the run once it took longer than limit, time spent paused does not count
+    fn _watchdog(finished: Arc<AtomicBool>, limit: Duration) {
         spawn(move || {
-            *state.write().unwrap() = State::Simulate;
-            let data_guard = data.read().unwrap();
-            if let Some(ref data) = *data_guard {
-                let data = data.clone();
-                let state = Arc::clone(&state);
-                //在一个线程的话无法继续监听hotkey
-                spawn(move || {
-                    match *loop_times.read().unwrap() {
-                        LoopTimes::Unlimited => {
-                            while *state.read().unwrap() != State::Stop {
-                                if let Err(_) = data.simulate() {
-                                    //show error to user
-                                    break;
-                                }
-                            }
+            while !finished.load(Ordering::SeqCst) {
+                if active_time() >= limit {
+                    abort(&format!("ran longer than {}s", limit.as_secs()));
+                    return;
+                }
+                sleep(WATCHDOG_POLL);
+            }
+        });
+    }
+    ///physical input is dropped only while the simulation thread is alive,
+    ///a dead thread must never leave the user without keyboard and mouse
+    fn is_locked(state: &Arc<RwLock<State>>, settings: &Arc<RwLock<Settings>>) -> bool {
+        settings.read().unwrap().input_lock
+            && *state.read().unwrap() == State::Simulate
+            && is_running()
+    }
+    ///failsafes that watch physical input, they only act while a simulation runs
+    fn check_failsafe(
+        state: &Arc<RwLock<State>>,
+        settings: &Arc<RwLock<Settings>>,
+        escapes: &Arc<Mutex<EscapeCounter>>,
+        event: &Event,
+    ) {
+        if !matches!(*state.read().unwrap(), State::Simulate | State::Paused) {
+            return;
+        }
+        let failsafe = settings.read().unwrap().failsafe;
+        match *event {
+            Event::MouseMove { x, y, .. }
+                if failsafe.corner && in_corner(x, y, failsafe.corner_margin as Float) =>
+            {
+                abort("mouse moved into a screen corner");
+            }
+            Event::KeyPress {
+                key: Key::Escape, ..
+            } if escapes.lock().unwrap().press(failsafe.escape_presses) => {
+                abort(&format!("Escape pressed {} times", failsafe.escape_presses));
+            }
+            _ => {}
+        }
+    }
+    ///store recorded data into the macro library, keep it in memory only if failed
+    fn save(database: &Arc<Mutex<Option<Database>>>, data: Data) -> Data {
+        match *database.lock().unwrap() {
+            Some(ref database) => match database.create(&data) {
+                Ok(stored) => stored,
+                Err(err) => {
+                    warn!("save data error: {:?}", err);
+                    data
+                }
+            },
+            None => data,
+        }
+    }
+    ///reload the selected macro from the library, or the latest one if nothing selected
+    fn load(database: &Arc<Mutex<Option<Database>>>, data: &Arc<RwLock<Option<Data>>>) {
+        let guard = database.lock().unwrap();
+        let database = match *guard {
+            Some(ref database) => database,
+            None => return,
+        };
+        let selected = data.read().unwrap().as_ref().and_then(|data| data.id);
+        let loaded = match selected {
+            Some(id) => database.read(id),
+            None => database.latest(),
+        };
+        match loaded {
+            Ok(Some(loaded)) => *data.write().unwrap() = Some(loaded),
+            Ok(None) => {}
+            Err(err) => warn!("load data error: {:?}", err),
+        }
+    }
+    ///list, select, rename and delete stored macros, export the current one or import one
+    fn show_library(&mut self, ctx: &egui::Context) {
+        let selected = self.data.read().unwrap().as_ref().and_then(|data| data.id);
+        //a recording stored since the last listing
+        if selected.is_some() && !self.library.iter().any(|data| data.id == selected) {
+            self.refresh();
+        }
+        egui::SidePanel::left("library").show(ctx, |ui| {
+            let mut clicked = None;
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    for data in &self.library {
+                        let label = format!("{} #{}", data.name, data.id.unwrap_or_default());
+                        if ui.selectable_label(data.id == selected, label).clicked() {
+                            clicked = Some(data.clone());
                         }
-                        LoopTimes::Limited(times) => {
-                            for _ in 0..times {
-                                if let Err(_) = data.simulate() {
-                                    //show error to user
-                                    break;
-                                }
-                            }
+                    }
+                });
+            if let Some(data) = clicked {
+                self.new_name = data.name.clone();
+                *self.data.write().unwrap() = Some(data);
+            }
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.new_name);
+                if ui.button("rename").clicked() {
+                    self.notice = Some(self.rename());
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("delete").clicked() {
+                    self.notice = Some(self.delete());
+                }
+                if ui.button("refresh").clicked() {
+                    self.refresh();
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("file");
+                ui.text_edit_singleline(&mut self.exchange_path);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("export").clicked() {
+                    self.notice = Some(self.export());
+                }
+                if ui.button("import").clicked() {
+                    self.notice = Some(self.import());
+                }
+            });
+            if let Some(ref notice) = self.notice {
+                ui.label(notice);
+            }
+        });
+    }
+    fn refresh(&mut self) {
+        let listed = match *self.database.lock().unwrap() {
+            Some(ref database) => database.list(),
+            None => return,
+        };
+        match listed {
+            Ok(library) => self.library = library,
+            Err(err) => {
+                warn!("list data error: {:?}", err);
+                self.notice = Some(format!("list error: {}", err));
+            }
+        }
+    }
+    ///the selected macro, if it is stored in the library
+    fn selected(&self) -> Option<Data> {
+        self.data
+            .read()
+            .unwrap()
+            .clone()
+            .filter(|data| data.id.is_some())
+    }
+    fn rename(&mut self) -> String {
+        let mut data = match self.selected() {
+            Some(data) => data,
+            None => return "select a macro first".to_string(),
+        };
+        data.name = self.new_name.trim().to_string();
+        if data.name.is_empty() {
+            return "a macro needs a name".to_string();
+        }
+        let updated = match *self.database.lock().unwrap() {
+            Some(ref database) => database.update(&data),
+            None => return "no library to rename in".to_string(),
+        };
+        let notice = match updated {
+            Ok(updated) => {
+                let notice = format!("renamed to {}", updated.name);
+                *self.data.write().unwrap() = Some(updated);
+                notice
+            }
+            Err(err) => {
+                warn!("rename error: {:?}", err);
+                format!("rename error: {}", err)
+            }
+        };
+        self.refresh();
+        notice
+    }
+    fn delete(&mut self) -> String {
+        let (id, name) = match self.selected() {
+            Some(Data {
+                id: Some(id), name, ..
+            }) => (id, name),
+            _ => return "select a macro first".to_string(),
+        };
+        let deleted = match *self.database.lock().unwrap() {
+            Some(ref database) => database.delete(id),
+            None => return "no library to delete from".to_string(),
+        };
+        let notice = match deleted {
+            Ok(()) => {
+                *self.data.write().unwrap() = None;
+                self.new_name.clear();
+                format!("deleted {}", name)
+            }
+            Err(err) => {
+                warn!("delete error: {:?}", err);
+                format!("delete error: {}", err)
+            }
+        };
+        self.refresh();
+        notice
+    }
+    fn export(&self) -> String {
+        Self::load(&self.database, &self.data);
+        let data = match *self.data.read().unwrap() {
+            Some(ref data) => data.clone(),
+            None => return "nothing to export yet".to_string(),
+        };
+        match export_file(&data, &self.exchange_path) {
+            Ok(()) => format!("exported {} to {}", data.name, self.exchange_path),
+            Err(err) => {
+                warn!("export error: {:?}", err);
+                format!("export error: {:?}", err)
+            }
+        }
+    }
+    ///store the imported macro in the library and select it
+    fn import(&self) -> String {
+        match import_file(&self.exchange_path) {
+            Ok(data) => {
+                let data = Self::save(&self.database, data);
+                let notice = format!("imported {} from {}", data.name, self.exchange_path);
+                *self.data.write().unwrap() = Some(data);
+                notice
+            }
+            Err(err) => {
+                warn!("import error: {:?}", err);
+                format!("import error: {:?}", err)
+            }
+        }
+    }
+    ///edit settings as json, applied ones are validated, saved and used right away
+    fn show_settings(&mut self, ctx: &egui::Context) {
+        egui::SidePanel::right("settings").show(ctx, |ui| {
+            ui.collapsing("settings", |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        ui.add(egui::TextEdit::multiline(&mut self.settings_text).code_editor());
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("apply").clicked() {
+                        self.settings_notice = Some(self.apply_settings());
+                    }
+                    if ui.button("revert").clicked() {
+                        self.settings_text = self.settings_json();
+                        self.settings_notice = None;
+                    }
+                });
+                if let Some(ref notice) = self.settings_notice {
+                    ui.label(notice);
+                }
+            });
+        });
+    }
+    fn settings_json(&self) -> String {
+        match serde_json::to_string_pretty(&*self.settings.read().unwrap()) {
+            Ok(json) => json,
+            Err(err) => {
+                warn!("settings json error: {:?}", err);
+                String::new()
+            }
+        }
+    }
+    fn apply_settings(&mut self) -> String {
+        let (settings, problems) = match Settings::parse(&self.settings_text) {
+            Ok(parsed) => parsed,
+            Err(err) => return format!("settings error: {}", err),
+        };
+        let saved = match *self.database.lock().unwrap() {
+            Some(ref database) => settings.save(database),
+            None => Ok(()),
+        };
+        if let Err(err) = saved {
+            warn!("save settings error: {:?}", err);
+            return format!("save settings error: {}", err);
+        }
+        *self.settings.write().unwrap() = settings;
+        self.settings_text = self.settings_json();
+        match problems.is_empty() {
+            true => "settings applied".to_string(),
+            false => format!("settings applied, {}", problems.join(", ")),
+        }
+    }
+    ///last simulation run and its failures
+    fn show_report(&self, ctx: &egui::Context) {
+        let mut reports = reports();
+        let report = match reports.pop() {
+            Some(report) => report,
+            None => return,
+        };
+        egui::TopBottomPanel::bottom("report").show(ctx, |ui| {
+            ui.label(Self::report_summary(&report));
+            for failure in &report.failures {
+                ui.colored_label(egui::Color32::RED, failure.to_string());
+            }
+            if !reports.is_empty() {
+                ui.collapsing(format!("earlier runs ({})", reports.len()), |ui| {
+                    for earlier in reports.iter().rev() {
+                        ui.label(Self::report_summary(earlier));
+                        for failure in &earlier.failures {
+                            ui.colored_label(egui::Color32::RED, failure.to_string());
                         }
-                    };
-                    //stop
-                    *state.write().unwrap() = State::Stop;
+                    }
                 });
-            } else {
-                *state.write().unwrap() = State::Stop;
             }
         });
+        if report.is_running() {
+            ctx.request_repaint_after(Duration::from_millis(500));
+        }
+    }
+    fn report_summary(report: &Report) -> String {
+        let status = match (report.is_running(), report.stopped) {
+            (true, _) => "running",
+            (false, true) => "stopped",
+            (false, false) if report.failures.is_empty() => "finished",
+            (false, false) => "failed",
+        };
+        format!(
+            "{}: {}, {} iteration(s) in {}s, seed {}",
+            report.name,
+            status,
+            report.iterations,
+            report.seconds(),
+            report.seed
+        )
     }
     fn _push(
         state: Arc<RwLock<State>>,
-        mouse_filter: Arc<RwLock<bool>>,
+        settings: &Arc<RwLock<Settings>>,
         event: Event,
         events: Arc<RwLock<Vec<Event>>>,
     ) {
         match *state.read().unwrap() {
-            State::Record => match (*mouse_filter.read().unwrap(), event) {
+            State::Record => match (settings.read().unwrap().mouse_filter, event) {
                 (true, Event::MouseMove { .. }) => {}
                 _ => {
                     let event = Event::build(event, events.read().unwrap().last());
@@ -200,6 +826,7 @@ enum State {
     Stop,
     Record,
     Simulate,
+    Paused,
 }
 
 impl State {
@@ -208,32 +835,12 @@ impl State {
     }
 }
 
-#[derive(Clone, Copy)]
-struct HotKey {
-    pub stop: Key,
-    pub record: Key,
-    pub simulate: Key,
-}
-
-impl HotKey {
-    fn default() -> Self {
-        Self {
-            record: Key::F10,
-            simulate: Key::F11,
-            stop: Key::F12,
-        }
-    }
-    fn contains(&self, key: &Key) -> bool {
-        let keys = vec![self.record, self.simulate, self.stop];
-        keys.contains(key)
-    }
-}
-
-#[derive(Clone, Copy)]
-#[allow(dead_code)]
-enum LoopTimes {
-    Unlimited,
-    Limited(Int),
+enum Action {
+    Record,
+    Simulate,
+    Pause,
+    Stop,
+    Run(Binding),
 }
 
 impl eframe::App for App {
@@ -242,6 +849,9 @@ impl eframe::App for App {
             self.capture.run(ctx.clone());
         }
 
+        self.show_report(ctx);
+        self.show_library(ctx);
+        self.show_settings(ctx);
         //capture app start
         egui::CentralPanel::default().show(ctx, |_ui| {
             if !self.capture.is_stop() {
@@ -261,3 +871,73 @@ impl eframe::App for App {
 //         ColorImage::from_rgba_unmultiplied([width as usize, height as usize], &pixels);
 //     ImageData::Color(Arc::new(color_image))
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common::SimulateSignal, event::install_mock};
+    use std::time::Instant;
+
+    ///run data to the end on the mock backend, returns how long it took
+    fn run_to_end(data: &str, loop_times: LoopTimes, settings: &Settings) -> Duration {
+        let state = Arc::new(RwLock::new(State::Stop));
+        let running = App::start(&state).unwrap();
+        let data = Data::new("test".to_string(), data.to_string());
+        let start = Instant::now();
+        App::run(
+            Arc::clone(&state),
+            running,
+            data,
+            loop_times,
+            Speed::default(),
+            settings,
+        );
+        //the thread lets go of Running right after it sets Stop
+        while *state.read().unwrap() != State::Stop || is_running() {
+            assert!(start.elapsed() < Duration::from_secs(5), "run never ended");
+            sleep(Duration::from_millis(5));
+        }
+        start.elapsed()
+    }
+
+    #[test]
+    fn loops_wait_loop_interval() {
+        let (_guard, mock) = install_mock();
+        let settings = Settings {
+            loop_interval: 100,
+            ..Settings::default()
+        };
+        let elapsed = run_to_end("key_click(Key::a);", LoopTimes::Limited(3), &settings);
+        assert_eq!(mock.trace().len(), 6);
+        //two waits, none before the first loop
+        assert!(
+            elapsed >= Duration::from_millis(200) && elapsed < Duration::from_millis(400),
+            "{:?}",
+            elapsed
+        );
+    }
+
+    #[test]
+    fn for_duration_leaves_out_pauses() {
+        let (_guard, mock) = install_mock();
+        let pause = spawn(|| {
+            sleep(Duration::from_millis(150));
+            simulate_state_send(SimulateSignal::Pause);
+            sleep(Duration::from_millis(400));
+            simulate_state_send(SimulateSignal::Resume);
+        });
+        let elapsed = run_to_end(
+            "delay(100);\nkey_click(Key::a);",
+            LoopTimes::ForDuration(300),
+            &Settings::default(),
+        );
+        pause.join().unwrap();
+        assert_eq!(mock.trace().len(), 6);
+        //300 running and 400 paused
+        assert!(
+            elapsed >= Duration::from_millis(650) && elapsed < Duration::from_millis(950),
+            "{:?}",
+            elapsed
+        );
+    }
+}
@@ -1,5 +1,6 @@
 #[allow(unused_imports)]
 use crate::i;
+use crate::settings::Settings;
 use crabgrab::prelude::*;
 use eframe::CreationContext;
 use egui::{ColorImage, ImageData};
@@ -8,7 +9,7 @@ use image::{ ImageBuffer, Rgba};
 use log::{info, warn};
 use pollster::block_on;
 use std::{
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{Arc, Mutex, MutexGuard, RwLock},
     thread::{spawn, JoinHandle},
     time::Duration,
 };
@@ -22,15 +23,18 @@ pub struct Capture {
     state: State,
     listen_handle: Option<JoinHandle<()>>,
     pub app: Arc<Mutex<App>>,
+    ///settings holding the part of the application identifier of the window to capture
+    settings: Arc<RwLock<Settings>>,
 }
 
 impl Capture {
-    pub fn new(_cc: &CreationContext) -> Self {
+    pub fn new(_cc: &CreationContext, settings: Arc<RwLock<Settings>>) -> Self {
         let app = App::new(_cc);
         Self {
             state: State::Stop,
             listen_handle: None,
             app: Arc::new(Mutex::new(app)),
+            settings,
         }
     }
     pub fn app(&self) -> MutexGuard<App> {
@@ -42,13 +46,15 @@ impl Capture {
     pub fn run(&mut self, ctx: egui::Context) {
         self.state = State::Run;
         let app_clone = Arc::clone(&self.app);
+        let target = self.settings.read().unwrap().capture_target.to_lowercase();
         self.listen_handle = Some(spawn(move || {
-            Self::_listen(app_clone, ctx)
+            Self::_listen(app_clone, ctx, target)
         }));
     }
     fn _listen(
         app: Arc<Mutex<App>>,
         ctx: egui::Context,
+        target: String,
     ) {
         let token = block_on(async {
             match CaptureStream::test_access(false) {
@@ -64,7 +70,7 @@ impl Capture {
             .windows()
             .filter(|window| {
                 let app_identifier = window.application().identifier();
-                window.title().len() != 0 && app_identifier.to_lowercase().contains("chrome")
+                window.title().len() != 0 && app_identifier.to_lowercase().contains(&target)
             })
             .next();
         match window {
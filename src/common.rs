@@ -7,7 +7,7 @@ use log::{info, warn};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use lazy_static::lazy_static;
 use std::sync::RwLock;
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 pub type Int = i32;
 pub type UInt = u32;
@@ -28,7 +28,46 @@ pub fn instant_elapse_millis() -> UInt {
     }
 }
 
-pub fn simulate_state_send(msg: bool) {
+///seconds since unix epoch
+pub fn timestamp() -> UInt {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as UInt,
+        Err(_) => 0,
+    }
+}
+
+///slowest and fastest playback speed a user can pick
+pub const MIN_SPEED: Float = 0.25;
+pub const MAX_SPEED: Float = 10.0;
+
+///playback speed of the running simulation, 2.0 means twice as fast
+pub fn set_speed(factor: Float) {
+    let factor = if factor.is_finite() && factor > 0 as Float {
+        factor
+    } else {
+        1 as Float
+    };
+    *SPEED.write().unwrap() = factor;
+}
+
+pub fn speed() -> Float {
+    *SPEED.read().unwrap()
+}
+
+///millis of a delay after the playback speed applied
+pub fn scale_millis(millis: u64) -> u64 {
+    (millis as Float / speed()).round() as u64
+}
+
+///control messages from the gui/hotkeys to the running simulation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimulateSignal {
+    Stop,
+    Pause,
+    Resume,
+}
+
+pub fn simulate_state_send(msg: SimulateSignal) {
     if let Err(err) = SIMULATE_STATE_CHANNEL.0.send(msg) {
         warn!("simulate_state_send error: {:?}", err);
     }
@@ -42,6 +81,8 @@ pub fn simulate_state_send(msg: bool) {
 
 lazy_static! {
     pub static ref NOW: RwLock<Option<Instant>> = RwLock::new(None);
-    pub static ref SIMULATE_STATE_CHANNEL: (Sender<bool>, Receiver<bool>) = unbounded();
+    pub static ref SPEED: RwLock<Float> = RwLock::new(1 as Float);
+    pub static ref SIMULATE_STATE_CHANNEL: (Sender<SimulateSignal>, Receiver<SimulateSignal>) =
+        unbounded();
     // pub static ref CAPTURE_CHANNEL: (Sender<Texture>, Receiver<Texture>) = unbounded();
 }
@@ -0,0 +1,27 @@
+use super::DatabaseError;
+use std::fmt;
+
+impl From<sqlite::Error> for DatabaseError {
+    fn from(err: sqlite::Error) -> Self {
+        DatabaseError::Sqlite(err)
+    }
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DatabaseError::Sqlite(err) => write!(f, "sqlite error: {}", err),
+            DatabaseError::MissingId => write!(f, "macro is not stored yet"),
+            DatabaseError::NotFound(id) => write!(f, "no macro with id {}", id),
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DatabaseError::Sqlite(err) => Some(err),
+            _ => None,
+        }
+    }
+}
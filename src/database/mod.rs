@@ -0,0 +1,370 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use crate::{
+    common::{timestamp, Int, UInt},
+    event::Data,
+};
+use sqlite::{Connection, State, Statement};
+mod impls;
+
+pub const DATABASE_PATH: &str = "db/wise_key.db";
+
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS macros (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    content TEXT NOT NULL,
+    resolution_width INTEGER NOT NULL DEFAULT 0,
+    resolution_height INTEGER NOT NULL DEFAULT 0,
+    os TEXT NOT NULL DEFAULT '',
+    os_version TEXT NOT NULL DEFAULT '',
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+)";
+
+const CREATE_SETTINGS_TABLE: &str = "CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+)";
+
+const COLUMNS: &str =
+    "id, name, content, resolution_width, resolution_height, os, os_version, created_at, updated_at";
+
+///macro library, every recorded Data is a row of table `macros`,
+///user settings are rows of table `settings`
+pub struct Database {
+    connection: Connection,
+}
+
+impl Database {
+    pub fn open(path: &str) -> Result<Self, DatabaseError> {
+        Self::migrate(Connection::open(path)?)
+    }
+
+    ///empty database in memory
+    #[cfg(test)]
+    pub fn memory() -> Self {
+        Self::migrate(Connection::open(":memory:").unwrap()).unwrap()
+    }
+
+    ///add the tables a database from an older version lacks, tables already there are kept
+    fn migrate(connection: Connection) -> Result<Self, DatabaseError> {
+        connection.execute(CREATE_TABLE)?;
+        connection.execute(CREATE_SETTINGS_TABLE)?;
+        Ok(Self { connection })
+    }
+
+    ///insert data as a new macro, returns the stored data with id and timestamps filled
+    pub fn create(&self, data: &Data) -> Result<Data, DatabaseError> {
+        let now = timestamp();
+        let mut statement = self.prepare(
+            "INSERT INTO macros (name, content, resolution_width, resolution_height, os, os_version, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        Self::bind(&mut statement, 1, data.name.as_str())?;
+        Self::bind(&mut statement, 2, data.content.as_str())?;
+        Self::bind(&mut statement, 3, data.resolution.0 as i64)?;
+        Self::bind(&mut statement, 4, data.resolution.1 as i64)?;
+        Self::bind(&mut statement, 5, data.os.as_str())?;
+        Self::bind(&mut statement, 6, data.os_version.as_str())?;
+        Self::bind(&mut statement, 7, now as i64)?;
+        Self::bind(&mut statement, 8, now as i64)?;
+        Self::step(&mut statement)?;
+        let id = self.last_insert_id()?;
+        match self.read(id)? {
+            Some(data) => Ok(data),
+            None => Err(DatabaseError::NotFound(id)),
+        }
+    }
+
+    pub fn read(&self, id: Int) -> Result<Option<Data>, DatabaseError> {
+        let mut statement = self.prepare(format!("SELECT {} FROM macros WHERE id = ?", COLUMNS))?;
+        Self::bind(&mut statement, 1, id as i64)?;
+        Self::fetch_one(&mut statement)
+    }
+
+    pub fn read_by_name(&self, name: &str) -> Result<Option<Data>, DatabaseError> {
+        let mut statement = self.prepare(format!(
+            "SELECT {} FROM macros WHERE name = ? ORDER BY updated_at DESC, id DESC LIMIT 1",
+            COLUMNS
+        ))?;
+        Self::bind(&mut statement, 1, name)?;
+        Self::fetch_one(&mut statement)
+    }
+
+    ///the most recently updated macro
+    pub fn latest(&self) -> Result<Option<Data>, DatabaseError> {
+        let mut statement = self.prepare(format!(
+            "SELECT {} FROM macros ORDER BY updated_at DESC, id DESC LIMIT 1",
+            COLUMNS
+        ))?;
+        Self::fetch_one(&mut statement)
+    }
+
+    ///update every field of the macro by data.id, updated_at is refreshed
+    pub fn update(&self, data: &Data) -> Result<Data, DatabaseError> {
+        let id = match data.id {
+            Some(id) => id,
+            None => return Err(DatabaseError::MissingId),
+        };
+        let mut statement = self.prepare(
+            "UPDATE macros SET name = ?, content = ?, resolution_width = ?, resolution_height = ?,
+            os = ?, os_version = ?, updated_at = ? WHERE id = ?",
+        )?;
+        Self::bind(&mut statement, 1, data.name.as_str())?;
+        Self::bind(&mut statement, 2, data.content.as_str())?;
+        Self::bind(&mut statement, 3, data.resolution.0 as i64)?;
+        Self::bind(&mut statement, 4, data.resolution.1 as i64)?;
+        Self::bind(&mut statement, 5, data.os.as_str())?;
+        Self::bind(&mut statement, 6, data.os_version.as_str())?;
+        Self::bind(&mut statement, 7, timestamp() as i64)?;
+        Self::bind(&mut statement, 8, id as i64)?;
+        Self::step(&mut statement)?;
+        if self.connection.change_count() == 0 {
+            return Err(DatabaseError::NotFound(id));
+        }
+        match self.read(id)? {
+            Some(data) => Ok(data),
+            None => Err(DatabaseError::NotFound(id)),
+        }
+    }
+
+    pub fn delete(&self, id: Int) -> Result<(), DatabaseError> {
+        let mut statement = self.prepare("DELETE FROM macros WHERE id = ?")?;
+        Self::bind(&mut statement, 1, id as i64)?;
+        Self::step(&mut statement)?;
+        if self.connection.change_count() == 0 {
+            return Err(DatabaseError::NotFound(id));
+        }
+        Ok(())
+    }
+
+    ///every macro, the most recently updated first
+    pub fn list(&self) -> Result<Vec<Data>, DatabaseError> {
+        let mut statement = self.prepare(format!(
+            "SELECT {} FROM macros ORDER BY updated_at DESC, id DESC",
+            COLUMNS
+        ))?;
+        let mut list = Vec::new();
+        while let State::Row = statement.next()? {
+            list.push(Self::row(&statement)?);
+        }
+        Ok(list)
+    }
+
+    ///every (key, value) of table `settings`
+    pub fn settings(&self) -> Result<Vec<(String, String)>, DatabaseError> {
+        let mut statement = self.prepare("SELECT key, value FROM settings ORDER BY key")?;
+        let mut settings = Vec::new();
+        while let State::Row = statement.next()? {
+            settings.push((
+                statement.read::<String, _>("key")?,
+                statement.read::<String, _>("value")?,
+            ));
+        }
+        Ok(settings)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<(), DatabaseError> {
+        let mut statement = self.prepare(
+            "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, ?)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        )?;
+        Self::bind(&mut statement, 1, key)?;
+        Self::bind(&mut statement, 2, value)?;
+        Self::bind(&mut statement, 3, timestamp() as i64)?;
+        Self::step(&mut statement)
+    }
+
+    ///changes whenever another connection commits, e.g. settings edited outside the app
+    pub fn data_version(&self) -> Result<Int, DatabaseError> {
+        let mut statement = self.prepare("PRAGMA data_version")?;
+        statement.next()?;
+        Ok(statement.read::<i64, _>(0)? as Int)
+    }
+
+    fn prepare<T: AsRef<str>>(&self, sql: T) -> Result<Statement<'_>, DatabaseError> {
+        Ok(self.connection.prepare(sql)?)
+    }
+
+    fn bind<T>(statement: &mut Statement, index: usize, value: T) -> Result<(), DatabaseError>
+    where
+        T: sqlite::BindableWithIndex,
+    {
+        Ok(statement.bind((index, value))?)
+    }
+
+    fn step(statement: &mut Statement) -> Result<(), DatabaseError> {
+        while let State::Row = statement.next()? {}
+        Ok(())
+    }
+
+    fn fetch_one(statement: &mut Statement) -> Result<Option<Data>, DatabaseError> {
+        match statement.next()? {
+            State::Row => Ok(Some(Self::row(statement)?)),
+            State::Done => Ok(None),
+        }
+    }
+
+    fn last_insert_id(&self) -> Result<Int, DatabaseError> {
+        let mut statement = self.prepare("SELECT last_insert_rowid()")?;
+        statement.next()?;
+        let id = statement.read::<i64, _>(0)?;
+        Ok(id as Int)
+    }
+
+    fn row(statement: &Statement) -> Result<Data, DatabaseError> {
+        let read_int = |column: &str| statement.read::<i64, _>(column);
+        let read_string = |column: &str| statement.read::<String, _>(column);
+        Ok(Data {
+            id: Some(read_int("id")? as Int),
+            name: read_string("name")?,
+            content: read_string("content")?,
+            resolution: (
+                read_int("resolution_width")? as Int,
+                read_int("resolution_height")? as Int,
+            ),
+            os: read_string("os")?,
+            os_version: read_string("os_version")?,
+            created_at: read_int("created_at")? as UInt,
+            updated_at: read_int("updated_at")? as UInt,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum DatabaseError {
+    Sqlite(sqlite::Error),
+    MissingId,
+    NotFound(Int),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(name: &str) -> Data {
+        let mut data = Data::new(name.to_string(), format!("key_click(Key::{});\n", name));
+        data.resolution = (1920, 1080);
+        data.os_version = "14".to_string();
+        data
+    }
+
+    #[test]
+    fn create_and_read() {
+        let database = Database::memory();
+        let created = database.create(&data("a")).unwrap();
+        let id = created.id.unwrap();
+        assert_eq!(created.name, "a");
+        assert_eq!(created.content, "key_click(Key::a);\n");
+        assert_eq!(created.resolution, (1920, 1080));
+        assert_eq!(created.os, std::env::consts::OS);
+        assert_eq!(created.os_version, "14");
+        assert!(created.created_at > 0);
+        assert_eq!(created.created_at, created.updated_at);
+        assert_eq!(database.read(id).unwrap().unwrap().name, "a");
+        assert!(database.read(id + 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn read_by_name_and_latest() {
+        let database = Database::memory();
+        assert!(database.latest().unwrap().is_none());
+        let first = database.create(&data("a")).unwrap();
+        let second = database.create(&data("b")).unwrap();
+        let again = database.create(&data("a")).unwrap();
+        //same timestamp, the higher id wins
+        assert_eq!(database.read_by_name("a").unwrap().unwrap().id, again.id);
+        assert_eq!(database.read_by_name("b").unwrap().unwrap().id, second.id);
+        assert!(database.read_by_name("c").unwrap().is_none());
+        assert_eq!(database.latest().unwrap().unwrap().id, again.id);
+        let ids: Vec<Option<Int>> = database
+            .list()
+            .unwrap()
+            .iter()
+            .map(|data| data.id)
+            .collect();
+        assert_eq!(ids, vec![again.id, second.id, first.id]);
+    }
+
+    #[test]
+    fn update_and_delete() {
+        let database = Database::memory();
+        let mut stored = database.create(&data("a")).unwrap();
+        stored.name = "renamed".to_string();
+        stored.content = "delay(10);\n".to_string();
+        let updated = database.update(&stored).unwrap();
+        assert_eq!(updated.id, stored.id);
+        assert_eq!(updated.name, "renamed");
+        assert_eq!(updated.content, "delay(10);\n");
+        assert_eq!(updated.created_at, stored.created_at);
+        let id = stored.id.unwrap();
+        database.delete(id).unwrap();
+        assert!(database.read(id).unwrap().is_none());
+        assert!(database.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_rows_are_errors() {
+        let database = Database::memory();
+        assert!(matches!(
+            database.update(&data("a")),
+            Err(DatabaseError::MissingId)
+        ));
+        let mut missing = data("a");
+        missing.id = Some(7);
+        assert!(matches!(
+            database.update(&missing),
+            Err(DatabaseError::NotFound(7))
+        ));
+        assert!(matches!(
+            database.delete(7),
+            Err(DatabaseError::NotFound(7))
+        ));
+    }
+
+    #[test]
+    fn settings_are_upserted() {
+        let database = Database::memory();
+        database.set_setting("speed", "1").unwrap();
+        database.set_setting("fidelity", "\"Raw\"").unwrap();
+        database.set_setting("speed", "2").unwrap();
+        assert_eq!(
+            database.settings().unwrap(),
+            vec![
+                ("fidelity".to_string(), "\"Raw\"".to_string()),
+                ("speed".to_string(), "2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn baseline_database_is_migrated() {
+        //the schema db/wise_key.db shipped with
+        let connection = Connection::open(":memory:").unwrap();
+        connection
+            .execute("CREATE TABLE users (name TEXT, age INTEGER)")
+            .unwrap();
+        connection
+            .execute("INSERT INTO users VALUES ('alice', 30)")
+            .unwrap();
+        let database = Database::migrate(connection).unwrap();
+        let created = database.create(&data("a")).unwrap();
+        assert_eq!(database.latest().unwrap().unwrap().id, created.id);
+        database.set_setting("speed", "1").unwrap();
+        assert_eq!(database.settings().unwrap().len(), 1);
+        {
+            let mut statement = database.prepare("SELECT name, age FROM users").unwrap();
+            assert_eq!(statement.next().unwrap(), State::Row);
+            assert_eq!(statement.read::<String, _>("name").unwrap(), "alice");
+            assert_eq!(statement.read::<i64, _>("age").unwrap(), 30);
+        }
+        //migrating twice keeps what is stored
+        let database = Database::migrate(database.connection).unwrap();
+        assert_eq!(database.list().unwrap().len(), 1);
+        assert_eq!(database.settings().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,294 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use super::{
+    synthetic::{expect, forget},
+    Event, GrabError, SimulateError,
+};
+use crate::common::{Float, UInt};
+use lazy_static::lazy_static;
+use rdev::{
+    display_size as _display_size, get_current_mouse_location as _get_current_mouse_location,
+    grab as _grab, simulate as _simulate, Event as _Event, EventType as _EventType,
+};
+use std::{
+    sync::{Arc, Mutex, RwLock},
+    time::Instant,
+};
+
+pub type GrabCallback = Box<dyn Fn(_Event) -> Option<_Event> + 'static>;
+
+///everything the simulation layer needs from the OS
+pub trait InputBackend: Send + Sync {
+    fn simulate(&self, event: Event) -> Result<(), SimulateError>;
+    fn grab(&self, callback: GrabCallback) -> Result<(), GrabError>;
+    fn current_point(&self) -> (Float, Float);
+    ///size of the main display, None if unknown
+    fn display_size(&self) -> Option<(Float, Float)>;
+}
+
+///talks to the OS through rdev, the default backend
+pub struct RdevBackend;
+
+impl InputBackend for RdevBackend {
+    fn simulate(&self, event: Event) -> Result<(), SimulateError> {
+        let _event: _EventType = event.into();
+        //grab may see the echo before simulate returns
+        expect(event);
+        match _simulate(&_event) {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                forget(event);
+                warn!("simulate event error: {:?}, {}", _event, err);
+                Err(SimulateError::Event(event))
+            }
+        }
+    }
+    fn grab(&self, callback: GrabCallback) -> Result<(), GrabError> {
+        match _grab(callback) {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                warn!("grab error: {:?}", err);
+                Err(err.into())
+            }
+        }
+    }
+    fn current_point(&self) -> (Float, Float) {
+        if let Some(point) = _get_current_mouse_location() {
+            (point.x as Float, point.y as Float)
+        } else {
+            (0 as Float, 0 as Float)
+        }
+    }
+    fn display_size(&self) -> Option<(Float, Float)> {
+        match _display_size() {
+            Ok((width, height)) => Some((width as Float, height as Float)),
+            Err(err) => {
+                warn!("display size error: {:?}", err);
+                None
+            }
+        }
+    }
+}
+
+///keeps every simulated event in memory instead of sending it to the OS,
+///elapse of the recorded event is the millis since the backend was created
+pub struct MockBackend {
+    start: Instant,
+    events: Mutex<Vec<Event>>,
+    point: Mutex<(Float, Float)>,
+    size: Mutex<Option<(Float, Float)>>,
+    ///refuse every event, as an OS without permission to simulate does
+    refuse: Mutex<bool>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            events: Mutex::new(Vec::new()),
+            point: Mutex::new((0 as Float, 0 as Float)),
+            size: Mutex::new(Some((1920 as Float, 1080 as Float))),
+            refuse: Mutex::new(false),
+        }
+    }
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+    ///events with elapse and duration zeroed, for comparing traces without timing
+    pub fn trace(&self) -> Vec<Event> {
+        self.events()
+            .into_iter()
+            .map(|event| event.set_elapse(0).set_duration(0))
+            .collect()
+    }
+    pub fn clear(&self) {
+        self.events.lock().unwrap().clear();
+    }
+    pub fn set_point(&self, x: Float, y: Float) {
+        *self.point.lock().unwrap() = (x, y);
+    }
+    pub fn set_display_size(&self, size: Option<(Float, Float)>) {
+        *self.size.lock().unwrap() = size;
+    }
+    pub fn set_refuse(&self, refuse: bool) {
+        *self.refuse.lock().unwrap() = refuse;
+    }
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputBackend for MockBackend {
+    fn simulate(&self, event: Event) -> Result<(), SimulateError> {
+        if *self.refuse.lock().unwrap() {
+            return Err(SimulateError::Event(event));
+        }
+        match event {
+            Event::MouseMove { x, y, .. }
+            | Event::Drag { x, y, .. }
+            | Event::ButtonPress { x, y, .. }
+            | Event::ButtonRelease { x, y, .. } => self.set_point(x, y),
+            _ => {}
+        }
+        let elapse = self.start.elapsed().as_millis() as UInt;
+        self.events.lock().unwrap().push(event.set_elapse(elapse));
+        Ok(())
+    }
+    fn grab(&self, _callback: GrabCallback) -> Result<(), GrabError> {
+        Ok(())
+    }
+    fn current_point(&self) -> (Float, Float) {
+        *self.point.lock().unwrap()
+    }
+    fn display_size(&self) -> Option<(Float, Float)> {
+        *self.size.lock().unwrap()
+    }
+}
+
+lazy_static! {
+    static ref BACKEND: RwLock<Arc<dyn InputBackend>> = RwLock::new(Arc::new(RdevBackend));
+}
+
+pub fn backend() -> Arc<dyn InputBackend> {
+    Arc::clone(&*BACKEND.read().unwrap())
+}
+
+///replace the backend used by simulate_event, grab and current_point
+pub fn set_backend(backend: Arc<dyn InputBackend>) {
+    *BACKEND.write().unwrap() = backend;
+}
+
+///install a fresh MockBackend, tests share the backend so they hold the guard until done
+#[cfg(test)]
+pub fn install_mock() -> (std::sync::MutexGuard<'static, ()>, Arc<MockBackend>) {
+    static LOCK: Mutex<()> = Mutex::new(());
+    let guard = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let mock = Arc::new(MockBackend::new());
+    set_backend(Arc::clone(&mock) as Arc<dyn InputBackend>);
+    (guard, mock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{Button, Data, Iteration, Key};
+
+    fn simulate(content: &str) -> Result<(), SimulateError> {
+        Data::new("test".to_string(), content.to_string())
+            .simulate(&Iteration { index: 0, count: 1 })
+    }
+
+    #[test]
+    fn key_click_presses_and_releases() {
+        let (_guard, mock) = install_mock();
+        simulate("key_click(Key::a);").unwrap();
+        assert_eq!(
+            mock.trace(),
+            vec![
+                Event::KeyPress {
+                    key: Key::KeyA,
+                    elapse: 0,
+                    duration: 0
+                },
+                Event::KeyRelease {
+                    key: Key::KeyA,
+                    elapse: 0,
+                    duration: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn click_at_point() {
+        let (_guard, mock) = install_mock();
+        simulate("mouse_move(10, 20);\nclick_right(30, 40);").unwrap();
+        assert_eq!(
+            mock.trace(),
+            vec![
+                Event::MouseMove {
+                    x: 10.0,
+                    y: 20.0,
+                    elapse: 0,
+                    duration: 0
+                },
+                Event::ButtonPress {
+                    button: Button::Right,
+                    x: 30.0,
+                    y: 40.0,
+                    elapse: 0,
+                    duration: 0
+                },
+                Event::ButtonRelease {
+                    button: Button::Right,
+                    x: 30.0,
+                    y: 40.0,
+                    elapse: 0,
+                    duration: 0
+                },
+            ]
+        );
+        assert_eq!(mock.current_point(), (30.0, 40.0));
+    }
+
+    #[test]
+    fn click_in_place_uses_current_point() {
+        let (_guard, mock) = install_mock();
+        mock.set_point(5.0, 6.0);
+        simulate("button_left_click();").unwrap();
+        assert_eq!(
+            mock.trace(),
+            vec![
+                Event::ButtonPress {
+                    button: Button::Left,
+                    x: 5.0,
+                    y: 6.0,
+                    elapse: 0,
+                    duration: 0
+                },
+                Event::ButtonRelease {
+                    button: Button::Left,
+                    x: 5.0,
+                    y: 6.0,
+                    elapse: 0,
+                    duration: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn wheel_and_loop_variables() {
+        let (_guard, mock) = install_mock();
+        simulate("if loop_index == 0 && loop_count == 1 { wheel(2, -3); }").unwrap();
+        assert_eq!(
+            mock.trace(),
+            vec![Event::Wheel {
+                x: 2,
+                y: -3,
+                elapse: 0,
+                duration: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn failure_keeps_events_before_it() {
+        let (_guard, mock) = install_mock();
+        assert!(simulate("key_press(Key::b);\nkey_press(no_such_key);").is_err());
+        assert_eq!(
+            mock.trace(),
+            vec![Event::KeyPress {
+                key: Key::KeyB,
+                elapse: 0,
+                duration: 0
+            }]
+        );
+    }
+}
@@ -0,0 +1,279 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use super::{rhai::key_variables, Key};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+///modifiers held down, left and right keys count the same
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+impl Modifiers {
+    fn flag(&mut self, key: Key) -> Option<&mut bool> {
+        match key {
+            Key::ControlLeft | Key::ControlRight => Some(&mut self.ctrl),
+            Key::ShiftLeft | Key::ShiftRight => Some(&mut self.shift),
+            Key::Alt | Key::AltGr => Some(&mut self.alt),
+            Key::MetaLeft | Key::MetaRight => Some(&mut self.meta),
+            _ => None,
+        }
+    }
+}
+
+///a key pressed while exactly these modifiers are held, e.g. Ctrl+Shift+F10,
+///stored as that text
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Chord {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl Chord {
+    pub fn new(key: Key) -> Self {
+        Self {
+            modifiers: Modifiers::default(),
+            key,
+        }
+    }
+    pub fn matches(&self, key: Key, modifiers: Modifiers) -> bool {
+        self.key == key && self.modifiers == modifiers
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChordError {
+    Empty,
+    UnknownKey(String),
+    ///only modifiers, e.g. "Ctrl+Shift"
+    MissingKey(String),
+    ///more than one key that is not a modifier
+    TooManyKeys(String),
+}
+
+fn key_by_name(name: &str) -> Option<Key> {
+    key_variables()
+        .into_iter()
+        .find(|(variable, _)| variable.eq_ignore_ascii_case(name))
+        .map(|(_, key)| key)
+        .or_else(|| name.parse().ok())
+}
+
+impl FromStr for Chord {
+    type Err = ChordError;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = Modifiers::default();
+        let mut key = None;
+        for name in text.split('+').map(str::trim) {
+            let modifier = match name.to_ascii_lowercase().as_str() {
+                "" => return Err(ChordError::Empty),
+                "ctrl" | "control" => Some(Key::ControlLeft),
+                "shift" => Some(Key::ShiftLeft),
+                "alt" | "option" => Some(Key::Alt),
+                "meta" | "super" | "win" | "cmd" | "command" => Some(Key::MetaLeft),
+                "primary" => Some(Key::primary_modifier()),
+                _ => None,
+            };
+            if let Some(flag) = modifier.and_then(|modifier| modifiers.flag(modifier)) {
+                *flag = true;
+                continue;
+            }
+            let found = key_by_name(name).ok_or_else(|| ChordError::UnknownKey(name.into()))?;
+            if key.replace(found).is_some() {
+                return Err(ChordError::TooManyKeys(text.into()));
+            }
+        }
+        match key {
+            Some(key) => Ok(Self { modifiers, key }),
+            None => Err(ChordError::MissingKey(text.into())),
+        }
+    }
+}
+
+impl TryFrom<String> for Chord {
+    type Error = ChordError;
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<Chord> for String {
+    fn from(chord: Chord) -> Self {
+        chord.to_string()
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = [
+            (self.modifiers.ctrl, "Ctrl"),
+            (self.modifiers.shift, "Shift"),
+            (self.modifiers.alt, "Alt"),
+            (self.modifiers.meta, "Meta"),
+        ];
+        for (_, name) in names.iter().filter(|(held, _)| *held) {
+            write!(f, "{}+", name)?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+impl fmt::Display for ChordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChordError::Empty => write!(f, "empty hotkey"),
+            ChordError::UnknownKey(name) => write!(f, "unknown key {:?}", name),
+            ChordError::MissingKey(text) => write!(f, "no key besides modifiers in {:?}", text),
+            ChordError::TooManyKeys(text) => write!(f, "more than one key in {:?}", text),
+        }
+    }
+}
+
+impl std::error::Error for ChordError {}
+
+///keys currently held on the physical keyboard, as seen by grab
+#[derive(Debug, Default)]
+pub struct KeyState {
+    ///each held key with the modifiers held when it went down
+    pressed: Vec<(Key, Modifiers)>,
+}
+
+impl KeyState {
+    pub fn modifiers(&self) -> Modifiers {
+        let mut modifiers = Modifiers::default();
+        for (key, _) in &self.pressed {
+            if let Some(flag) = modifiers.flag(*key) {
+                *flag = true;
+            }
+        }
+        modifiers
+    }
+    ///returns the modifiers the key is pressed with, auto repeat keeps the first ones
+    pub fn press(&mut self, key: Key) -> Modifiers {
+        if let Some((_, modifiers)) = self.pressed.iter().find(|(held, _)| *held == key) {
+            return *modifiers;
+        }
+        let modifiers = self.modifiers();
+        self.pressed.push((key, modifiers));
+        modifiers
+    }
+    ///returns the modifiers the key was pressed with, so releasing Ctrl before F10 still counts
+    pub fn release(&mut self, key: Key) -> Modifiers {
+        match self.pressed.iter().position(|(held, _)| *held == key) {
+            Some(index) => self.pressed.remove(index).1,
+            None => self.modifiers(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(text: &str) -> Chord {
+        text.parse().unwrap()
+    }
+
+    fn held(ctrl: bool, shift: bool, alt: bool, meta: bool) -> Modifiers {
+        Modifiers {
+            ctrl,
+            shift,
+            alt,
+            meta,
+        }
+    }
+
+    #[test]
+    fn every_chord_round_trips() {
+        for modifiers in 0..16 {
+            let modifiers = held(
+                modifiers & 1 != 0,
+                modifiers & 2 != 0,
+                modifiers & 4 != 0,
+                modifiers & 8 != 0,
+            );
+            for key in [
+                Key::F10,
+                Key::KeyA,
+                Key::Space,
+                Key::Num1,
+                Key::Unknown(300),
+            ] {
+                let chord = Chord { modifiers, key };
+                assert_eq!(chord.to_string().parse(), Ok(chord), "{}", chord);
+                let json = serde_json::to_string(&chord).unwrap();
+                assert_eq!(json, format!("{:?}", chord.to_string()));
+                assert_eq!(serde_json::from_str::<Chord>(&json).unwrap(), chord);
+            }
+        }
+        assert_eq!(chord("Ctrl+Shift+F10").to_string(), "Ctrl+Shift+F10");
+    }
+
+    #[test]
+    fn names_are_forgiving() {
+        assert_eq!(chord(" control + f10 "), chord("Ctrl+F10"));
+        assert_eq!(chord("cmd+a"), chord("Meta+a"));
+        assert_eq!(chord("option+Escape"), chord("Alt+Escape"));
+        assert_eq!(chord("Shift+Ctrl+F1"), chord("Ctrl+Shift+F1"));
+        let primary = match Key::primary_modifier() {
+            Key::MetaLeft => chord("Meta+s"),
+            _ => chord("Ctrl+s"),
+        };
+        assert_eq!(chord("primary+s"), primary);
+    }
+
+    #[test]
+    fn every_error_is_reported() {
+        assert_eq!("".parse::<Chord>(), Err(ChordError::Empty));
+        assert_eq!("Ctrl++F1".parse::<Chord>(), Err(ChordError::Empty));
+        assert_eq!(
+            "Ctrl+Nope".parse::<Chord>(),
+            Err(ChordError::UnknownKey("Nope".to_string()))
+        );
+        assert_eq!(
+            "Ctrl+Shift".parse::<Chord>(),
+            Err(ChordError::MissingKey("Ctrl+Shift".to_string()))
+        );
+        assert_eq!(
+            "F1+F2".parse::<Chord>(),
+            Err(ChordError::TooManyKeys("F1+F2".to_string()))
+        );
+        assert!(serde_json::from_str::<Chord>("\"Ctrl+\"").is_err());
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly() {
+        let chord = chord("Ctrl+F10");
+        assert!(chord.matches(Key::F10, held(true, false, false, false)));
+        assert!(!chord.matches(Key::F10, Modifiers::default()));
+        assert!(!chord.matches(Key::F10, held(true, true, false, false)));
+        assert!(!chord.matches(Key::F11, held(true, false, false, false)));
+        assert!(Chord::new(Key::F10).matches(Key::F10, Modifiers::default()));
+    }
+
+    #[test]
+    fn key_state_remembers_modifiers_of_each_press() {
+        let ctrl = held(true, false, false, false);
+        let mut keys = KeyState::default();
+        assert_eq!(keys.press(Key::ControlRight), Modifiers::default());
+        assert_eq!(keys.modifiers(), ctrl);
+        assert_eq!(keys.press(Key::F10), ctrl);
+        //auto repeat keeps the modifiers of the first press
+        keys.release(Key::ControlRight);
+        assert_eq!(keys.press(Key::F10), ctrl);
+        //Ctrl went up first, the release still counts as Ctrl+F10
+        assert_eq!(keys.release(Key::F10), ctrl);
+        assert_eq!(keys.modifiers(), Modifiers::default());
+        //a release never seen pressed takes what is held now
+        keys.press(Key::ShiftLeft);
+        assert_eq!(keys.release(Key::KeyA), held(false, true, false, false));
+    }
+}
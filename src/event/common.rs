@@ -3,24 +3,24 @@ use crate::i;
 #[allow(unused_imports)]
 use log::{info, warn};
 
-use super::{rhai::run, Data, Event};
-use crate::common::{Float, Int, UInt};
-use rdev::{
-    get_current_mouse_location as _get_current_mouse_location, grab as _grab, listen as _listen,
-    simulate as _simulate, stop_listen as _stop_listen, Event as _Event, EventType as _EventType,
+use super::{
+    backend::backend,
+    compact::{compact, gestures_to_script, Fidelity},
+    failsafe::check_event_rate,
+    held::track,
+    rhai::{eval_predicate, run},
+    simplify::{simplify_moves, Simplify},
+    Data, Event,
 };
+use crate::common::{Float, Int, UInt};
+use rdev::{listen as _listen, stop_listen as _stop_listen, Event as _Event};
 use rhai::EvalAltResult;
+use std::{fs, process::Command};
 pub fn grab<T>(callback: T) -> Result<(), GrabError>
 where
     T: Fn(_Event) -> Option<_Event> + 'static,
 {
-    match _grab(callback) {
-        Ok(_) => Ok(()),
-        Err(err) => {
-            warn!("grab error: {:?}", err);
-            Err(err.into())
-        }
-    }
+    backend().grab(Box::new(callback))
 }
 
 #[allow(dead_code)]
@@ -42,27 +42,27 @@ pub fn stop_listen() {
 }
 
 pub fn current_point() -> (Float, Float) {
-    if let Some(point) = _get_current_mouse_location() {
-        (point.x as Float, point.y as Float)
-    } else {
-        (0 as Float, 0 as Float)
-    }
+    backend().current_point()
 }
 
 pub fn simulate_event(event: Event) -> Result<(), SimulateError> {
-    let _event: _EventType = event.into();
-    match _simulate(&_event) {
-        Ok(_) => Ok(()),
-        Err(err) => {
-            warn!("simulate event error: {:?}", _event);
-            Err(err.into())
-        }
-    }
+    check_event_rate()?;
+    backend().simulate(event)?;
+    track(&event);
+    Ok(())
 }
 
-pub fn simulate_event_data(event_data: &Data) -> Result<(), SimulateError> {
-    let content = event_data.content.clone();
-    match run(content) {
+///position of a run inside its loop, exposed to scripts as loop_index and loop_count
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Iteration {
+    ///counted from 0
+    pub index: Int,
+    ///-1 when the loop does not run a fixed number of times
+    pub count: Int,
+}
+
+pub fn simulate_event_data(event_data: &Data, iteration: &Iteration) -> Result<(), SimulateError> {
+    match run(&event_data.content, iteration) {
         Err(err) => {
             warn!("simulate file error: {:?}", err);
             Err(err)
@@ -71,57 +71,62 @@ pub fn simulate_event_data(event_data: &Data) -> Result<(), SimulateError> {
     }
 }
 
-pub fn events_to_data(events: &Vec<Event>) -> Data {
-    let mut content = String::new();
-    events.iter().for_each(|event| {
-        content += &event.to_string();
-    });
-    Data::new("undefined".to_string(), content)
+pub fn simulate_predicate(predicate: &str, iteration: &Iteration) -> Result<bool, SimulateError> {
+    match eval_predicate(predicate, iteration) {
+        Err(err) => {
+            warn!("loop condition error: {:?}", err);
+            Err(err)
+        }
+        Ok(value) => Ok(value),
+    }
 }
 
-pub fn virtual_path<T, F, I, G>(
-    from_x: T,
-    from_y: F,
-    to_x: I,
-    to_y: G,
-    duration: UInt,
-) -> Option<Vec<(Int, Int, UInt)>>
-where
-    T: Into<Float>,
-    F: Into<Float>,
-    I: Into<Float>,
-    G: Into<Float>,
-{
-    let mut from_x = from_x.into() as Int;
-    let mut from_y = from_y.into() as Int;
-    let to_x = to_x.into() as Int;
-    let to_y = to_y.into() as Int;
-    let mut points = Vec::new();
-    let caculate_callback = |from: Int, to: Int| {
-        if from < to {
-            return from + 1;
+///simplify only applies to compact scripts, raw ones keep every recorded move
+pub fn events_to_data(events: &Vec<Event>, fidelity: Fidelity, simplify: &Simplify) -> Data {
+    let content = match fidelity {
+        Fidelity::Raw => {
+            let mut content = String::new();
+            events.iter().for_each(|event| {
+                content += &event.to_string();
+            });
+            content
         }
-        if from > to {
-            return from - 1;
-        }
-        from
+        Fidelity::Compact => gestures_to_script(&compact(&simplify_moves(events, simplify))),
     };
-    while from_x != to_x && from_y != to_y {
-        from_x = caculate_callback(from_x, to_x);
-        from_y = caculate_callback(from_y, to_y);
-        points.push((from_x, from_y, 0));
+    let mut data = Data::new("undefined".to_string(), content);
+    if let Some((width, height)) = backend().display_size() {
+        data.resolution = (width as Int, height as Int);
     }
-    let len = points.len() as UInt;
-    if len > 0 {
-        let each_duration = duration / len;
-        points.iter_mut().for_each(|point| {
-            point.2 = each_duration;
-        });
-        return Some(points);
+    data.os_version = os_version();
+    data
+}
+
+///version of the running OS, empty when it cannot be read
+fn os_version() -> String {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("sw_vers").arg("-productVersion").output()
+    } else if cfg!(target_os = "windows") {
+        Command::new("cmd").args(["/C", "ver"]).output()
+    } else {
+        return fs::read_to_string("/proc/sys/kernel/osrelease")
+            .map(|release| release.trim().to_string())
+            .unwrap_or_default();
+    };
+    let text = match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        Err(err) => {
+            warn!("os version unknown: {}", err);
+            return String::new();
+        }
+    };
+    //windows answers "Microsoft Windows [Version 10.0.19045.3803]"
+    match text.split_once("Version ") {
+        Some((_, version)) => version.trim_end_matches(']').to_string(),
+        None => text,
     }
-    None
 }
 
+#[derive(Debug)]
 pub enum GrabError {
     /// MacOS
     EventTapError,
@@ -140,6 +145,7 @@ pub enum GrabError {
     IoError(std::io::Error),
 }
 
+#[derive(Debug)]
 pub enum ListenError {
     /// MacOS
     EventTapError,
@@ -164,5 +170,41 @@ pub enum ListenError {
 #[derive(Debug)]
 pub enum SimulateError {
     Default,
+    Stopped,
+    ///the OS refused the event
+    Event(Event),
+    Clipboard(String),
     Rhai(Box<EvalAltResult>),
 }
+
+///elapse is the running sum of durations, as a recording or a parsed script has it
+#[cfg(test)]
+pub(super) fn timeline(events: Vec<Event>) -> Vec<Event> {
+    let mut elapse = 0;
+    events
+        .into_iter()
+        .map(|event| {
+            elapse += event.duration();
+            event.set_elapse(elapse)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::backend::install_mock;
+
+    #[test]
+    fn recordings_know_where_they_were_made() {
+        let (_guard, mock) = install_mock();
+        mock.set_display_size(Some((2560.0, 1440.0)));
+        let data = events_to_data(&vec![], Fidelity::Raw, &Simplify::default());
+        mock.set_display_size(None);
+        assert_eq!(data.resolution, (2560, 1440));
+        assert_eq!(data.os, std::env::consts::OS);
+        assert_eq!(data.os_version, os_version());
+        let data = events_to_data(&vec![], Fidelity::Raw, &Simplify::default());
+        assert_eq!(data.resolution, (0, 0));
+    }
+}
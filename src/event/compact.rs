@@ -0,0 +1,580 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use super::{Button, Event, Key};
+use crate::common::{Float, UInt};
+use serde::{Deserialize, Serialize};
+
+///how much of a recording ends up in the script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Fidelity {
+    ///one statement per recorded event
+    Raw,
+    ///clicks, double clicks, drags and typing become one call each
+    #[default]
+    Compact,
+}
+
+///press and release this close, in pixels, are one click
+const CLICK_TOLERANCE: Float = 2.0;
+///longest millis from the first release to the second press of a double click
+const DOUBLE_CLICK_GAP: UInt = 500;
+///fewer key clicks in a row stay single key_click calls
+const TYPING_MIN_KEYS: usize = 3;
+///a longer pause, in millis, between two key clicks ends a typing burst
+const TYPING_MAX_GAP: UInt = 1000;
+
+///characters type_keys can write, all unshifted and none that ends a statement or an argument
+pub const TYPED_KEYS: [(char, Key); 44] = [
+    ('a', Key::KeyA),
+    ('b', Key::KeyB),
+    ('c', Key::KeyC),
+    ('d', Key::KeyD),
+    ('e', Key::KeyE),
+    ('f', Key::KeyF),
+    ('g', Key::KeyG),
+    ('h', Key::KeyH),
+    ('i', Key::KeyI),
+    ('j', Key::KeyJ),
+    ('k', Key::KeyK),
+    ('l', Key::KeyL),
+    ('m', Key::KeyM),
+    ('n', Key::KeyN),
+    ('o', Key::KeyO),
+    ('p', Key::KeyP),
+    ('q', Key::KeyQ),
+    ('r', Key::KeyR),
+    ('s', Key::KeyS),
+    ('t', Key::KeyT),
+    ('u', Key::KeyU),
+    ('v', Key::KeyV),
+    ('w', Key::KeyW),
+    ('x', Key::KeyX),
+    ('y', Key::KeyY),
+    ('z', Key::KeyZ),
+    ('0', Key::Num0),
+    ('1', Key::Num1),
+    ('2', Key::Num2),
+    ('3', Key::Num3),
+    ('4', Key::Num4),
+    ('5', Key::Num5),
+    ('6', Key::Num6),
+    ('7', Key::Num7),
+    ('8', Key::Num8),
+    ('9', Key::Num9),
+    (' ', Key::Space),
+    ('-', Key::Minus),
+    ('=', Key::Equal),
+    ('.', Key::Dot),
+    ('\'', Key::Quote),
+    ('`', Key::BackQuote),
+    ('[', Key::LeftBracket),
+    (']', Key::RightBracket),
+];
+
+pub fn typed_key(character: char) -> Option<Key> {
+    TYPED_KEYS
+        .iter()
+        .find(|(typed, _)| *typed == character)
+        .map(|(_, key)| *key)
+}
+
+fn typed_char(key: Key) -> Option<char> {
+    TYPED_KEYS
+        .iter()
+        .find(|(_, typed)| *typed == key)
+        .map(|(character, _)| *character)
+}
+
+///one script statement, a recorded event or several of them folded together
+#[derive(Debug, Clone, PartialEq)]
+pub enum Gesture {
+    Event(Event),
+    KeyClick {
+        key: Key,
+        hold: UInt,
+    },
+    Click {
+        button: Button,
+        x: Float,
+        y: Float,
+        hold: UInt,
+    },
+    DoubleClick {
+        button: Button,
+        x: Float,
+        y: Float,
+        hold: UInt,
+        gap: UInt,
+    },
+    Drag {
+        button: Button,
+        from: (Float, Float),
+        to: (Float, Float),
+        duration: UInt,
+    },
+    ///move on from the previous mouse move, the wait before it is spent moving
+    Glide {
+        x: Float,
+        y: Float,
+        duration: UInt,
+    },
+    ///hold is the average press, interval the average from one press to the next
+    Type {
+        text: String,
+        hold: UInt,
+        interval: UInt,
+    },
+}
+
+impl Gesture {
+    ///rhai statement of the gesture without the delay before it
+    pub fn call(&self) -> String {
+        match self {
+            Gesture::Event(event) => event.call(),
+            Gesture::KeyClick { key, hold } => format!("key_click({},{});\n", key.script(), hold),
+            Gesture::Click { button, x, y, hold } => match button {
+                Button::Left => format!("click_left({},{},{});\n", x, y, hold),
+                Button::Right => format!("click_right({},{},{});\n", x, y, hold),
+                button => format!("click({},{},{},{});\n", button.script(), x, y, hold),
+            },
+            Gesture::DoubleClick {
+                button,
+                x,
+                y,
+                hold,
+                gap,
+            } => format!(
+                "double_click({},{},{},{},{});\n",
+                button.script(),
+                x,
+                y,
+                hold,
+                gap
+            ),
+            Gesture::Drag {
+                button,
+                from,
+                to,
+                duration,
+            } => {
+                let points = format!("{},{},{},{},{}", from.0, from.1, to.0, to.1, duration);
+                match button {
+                    Button::Left => format!("drag_left({});\n", points),
+                    Button::Right => format!("drag_right({});\n", points),
+                    button => format!("drag({},{});\n", button.script(), points),
+                }
+            }
+            Gesture::Glide { x, y, duration } => {
+                format!("mouse_move({},{},{});\n", x, y, duration)
+            }
+            Gesture::Type {
+                text,
+                hold,
+                interval,
+            } => format!("type_keys(\"{}\",{},{});\n", text, hold, interval),
+        }
+    }
+}
+
+///fold events into gestures, each paired with the millis to wait before it
+pub fn compact(events: &[Event]) -> Vec<(UInt, Gesture)> {
+    let mut gestures = Vec::new();
+    let mut index = 0;
+    while index < events.len() {
+        let rest = &events[index..];
+        if let Some(gesture) = glide(&events[..=index]) {
+            gestures.push((0, gesture));
+            index += 1;
+            continue;
+        }
+        let (count, gesture) = double_click(rest)
+            .or_else(|| typing(rest))
+            .or_else(|| key_click(rest))
+            .or_else(|| click(rest))
+            .or_else(|| drag(rest))
+            .unwrap_or((1, Gesture::Event(rest[0])));
+        gestures.push((rest[0].duration(), gesture));
+        index += count;
+    }
+    gestures
+}
+
+pub fn gestures_to_script(gestures: &[(UInt, Gesture)]) -> String {
+    let mut content = String::new();
+    for (delay, gesture) in gestures {
+        if *delay > 0 {
+            content += &format!("delay({});\n", delay);
+        }
+        content += &gesture.call();
+    }
+    content
+}
+
+fn near(a: (Float, Float), b: (Float, Float)) -> bool {
+    (a.0 - b.0).hypot(a.1 - b.1) <= CLICK_TOLERANCE
+}
+
+///a key press right followed by its release, with the millis it was held
+fn key_pair(events: &[Event]) -> Option<(Key, UInt)> {
+    match events {
+        [Event::KeyPress { key, .. }, Event::KeyRelease {
+            key: released,
+            duration,
+            ..
+        }, ..]
+            if key == released =>
+        {
+            Some((*key, *duration))
+        }
+        _ => None,
+    }
+}
+
+///a button press right followed by its release at the same point
+fn button_pair(events: &[Event]) -> Option<(Button, Float, Float, UInt)> {
+    match events {
+        [Event::ButtonPress { button, x, y, .. }, Event::ButtonRelease {
+            button: released,
+            x: release_x,
+            y: release_y,
+            duration,
+            ..
+        }, ..]
+            if button == released && near((*x, *y), (*release_x, *release_y)) =>
+        {
+            Some((*button, *x, *y, *duration))
+        }
+        _ => None,
+    }
+}
+
+fn double_click(events: &[Event]) -> Option<(usize, Gesture)> {
+    let (button, x, y, hold) = button_pair(events)?;
+    let (second, second_x, second_y, _) = button_pair(events.get(2..)?)?;
+    let gap = events[2].duration();
+    if second != button || !near((x, y), (second_x, second_y)) || gap > DOUBLE_CLICK_GAP {
+        return None;
+    }
+    Some((
+        4,
+        Gesture::DoubleClick {
+            button,
+            x,
+            y,
+            hold,
+            gap,
+        },
+    ))
+}
+
+fn typing(events: &[Event]) -> Option<(usize, Gesture)> {
+    let mut text = String::new();
+    let mut holds = 0;
+    let mut count = 0;
+    while let Some((key, hold)) = key_pair(&events[count..]) {
+        let character = match typed_char(key) {
+            Some(character) => character,
+            None => break,
+        };
+        if count > 0 && events[count].duration() > TYPING_MAX_GAP {
+            break;
+        }
+        text.push(character);
+        holds += hold;
+        count += 2;
+    }
+    if text.len() < TYPING_MIN_KEYS {
+        return None;
+    }
+    let keys = text.len() as UInt;
+    let hold = holds / keys;
+    //first press to last release, less one hold is the time between the first and last press
+    let span: UInt = events[1..count].iter().map(|event| event.duration()).sum();
+    let interval = span.saturating_sub(hold) / (keys - 1);
+    Some((
+        count,
+        Gesture::Type {
+            text,
+            hold,
+            interval,
+        },
+    ))
+}
+
+///a mouse move right after another, replay interpolates between the two
+fn glide(events: &[Event]) -> Option<Gesture> {
+    match events {
+        [.., Event::MouseMove { .. }, Event::MouseMove { x, y, duration, .. }] => {
+            Some(Gesture::Glide {
+                x: *x,
+                y: *y,
+                duration: *duration,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn key_click(events: &[Event]) -> Option<(usize, Gesture)> {
+    let (key, hold) = key_pair(events)?;
+    Some((2, Gesture::KeyClick { key, hold }))
+}
+
+fn click(events: &[Event]) -> Option<(usize, Gesture)> {
+    let (button, x, y, hold) = button_pair(events)?;
+    Some((2, Gesture::Click { button, x, y, hold }))
+}
+
+fn drag(events: &[Event]) -> Option<(usize, Gesture)> {
+    let (button, from) = match events.first()? {
+        Event::Drag { button, x, y, .. } => (*button, (*x, *y)),
+        _ => return None,
+    };
+    let count = events
+        .iter()
+        .take_while(
+            |event| matches!(event, Event::Drag { button: dragged, .. } if *dragged == button),
+        )
+        .count();
+    if count < 2 {
+        return None;
+    }
+    let to = match events[count - 1] {
+        Event::Drag { x, y, .. } => (x, y),
+        _ => from,
+    };
+    let duration = events[1..count].iter().map(|event| event.duration()).sum();
+    Some((
+        count,
+        Gesture::Drag {
+            button,
+            from,
+            to,
+            duration,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{common::timeline, script_to_events};
+
+    fn press(key: Key, duration: UInt) -> Event {
+        Event::KeyPress {
+            key,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    fn release(key: Key, duration: UInt) -> Event {
+        Event::KeyRelease {
+            key,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    fn button_press(button: Button, duration: UInt) -> Event {
+        Event::ButtonPress {
+            button,
+            x: 10.0,
+            y: 20.0,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    fn button_release(button: Button, duration: UInt) -> Event {
+        Event::ButtonRelease {
+            button,
+            x: 11.0,
+            y: 20.0,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    fn dragged(x: Float, y: Float, duration: UInt) -> Event {
+        Event::Drag {
+            button: Button::Left,
+            x,
+            y,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    fn moved(x: Float, y: Float, duration: UInt) -> Event {
+        Event::MouseMove {
+            x,
+            y,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    ///recordings and the compact script each should become
+    fn cases() -> Vec<(Vec<Event>, &'static str)> {
+        vec![
+            (
+                vec![press(Key::KeyA, 20), release(Key::KeyA, 40)],
+                "delay(20);\nkey_click(Key::a,40);\n",
+            ),
+            //too few keys to be typing
+            (
+                vec![
+                    press(Key::KeyA, 0),
+                    release(Key::KeyA, 40),
+                    press(Key::KeyB, 60),
+                    release(Key::KeyB, 30),
+                ],
+                "key_click(Key::a,40);\ndelay(60);\nkey_click(Key::b,30);\n",
+            ),
+            //holds 30, 50 and 40 average to 40, presses 100 then 160 apart average to 130
+            (
+                vec![
+                    press(Key::KeyA, 0),
+                    release(Key::KeyA, 30),
+                    press(Key::KeyB, 70),
+                    release(Key::KeyB, 50),
+                    press(Key::KeyC, 110),
+                    release(Key::KeyC, 40),
+                ],
+                "type_keys(\"abc\",40,130);\n",
+            ),
+            //a gap of TYPING_MAX_GAP still types
+            (
+                vec![
+                    press(Key::KeyA, 0),
+                    release(Key::KeyA, 10),
+                    press(Key::KeyB, 1000),
+                    release(Key::KeyB, 10),
+                    press(Key::KeyC, 10),
+                    release(Key::KeyC, 10),
+                ],
+                "type_keys(\"abc\",10,515);\n",
+            ),
+            //a longer one splits the burst, leaving too few keys on each side
+            (
+                vec![
+                    press(Key::KeyA, 0),
+                    release(Key::KeyA, 10),
+                    press(Key::KeyB, 1001),
+                    release(Key::KeyB, 10),
+                    press(Key::KeyC, 10),
+                    release(Key::KeyC, 10),
+                ],
+                "key_click(Key::a,10);\ndelay(1001);\nkey_click(Key::b,10);\ndelay(10);\nkey_click(Key::c,10);\n",
+            ),
+            //a release of another key is no click
+            (
+                vec![press(Key::KeyH, 0), release(Key::KeyI, 10)],
+                "key_press(Key::h);\ndelay(10);\nkey_release(Key::i);\n",
+            ),
+            //keys type_keys cannot write stay key clicks
+            (
+                vec![
+                    press(Key::F1, 0),
+                    release(Key::F1, 10),
+                    press(Key::F2, 10),
+                    release(Key::F2, 10),
+                    press(Key::F3, 10),
+                    release(Key::F3, 10),
+                ],
+                "key_click(Key::F1,10);\ndelay(10);\nkey_click(Key::F2,10);\ndelay(10);\nkey_click(Key::F3,10);\n",
+            ),
+            (
+                vec![
+                    button_press(Button::Left, 0),
+                    button_release(Button::Left, 60),
+                ],
+                "click_left(10,20,60);\n",
+            ),
+            (
+                vec![
+                    button_press(Button::Middle, 0),
+                    button_release(Button::Middle, 60),
+                ],
+                "click(Button::Middle,10,20,60);\n",
+            ),
+            //a gap of DOUBLE_CLICK_GAP is still a double click
+            (
+                vec![
+                    button_press(Button::Left, 0),
+                    button_release(Button::Left, 50),
+                    button_press(Button::Left, 500),
+                    button_release(Button::Left, 50),
+                ],
+                "double_click(Button::Left,10,20,50,500);\n",
+            ),
+            (
+                vec![
+                    button_press(Button::Left, 0),
+                    button_release(Button::Left, 50),
+                    button_press(Button::Left, 501),
+                    button_release(Button::Left, 50),
+                ],
+                "click_left(10,20,50);\ndelay(501);\nclick_left(10,20,50);\n",
+            ),
+            (
+                vec![
+                    dragged(0.0, 0.0, 0),
+                    dragged(5.0, 5.0, 16),
+                    dragged(10.0, 10.0, 16),
+                ],
+                "drag_left(0,0,10,10,32);\n",
+            ),
+            (
+                vec![moved(1.0, 1.0, 5), moved(2.0, 2.0, 16)],
+                "delay(5);\nmouse_move(1,1);\nmouse_move(2,2,16);\n",
+            ),
+        ]
+    }
+
+    #[test]
+    fn recordings_compact_to_scripts() {
+        for (events, expected) in cases() {
+            let events = timeline(events);
+            assert_eq!(
+                gestures_to_script(&compact(&events)),
+                expected,
+                "{:?}",
+                events
+            );
+        }
+    }
+
+    #[test]
+    fn replay_keeps_the_timeline() {
+        for (events, script) in cases() {
+            let events = timeline(events);
+            let parsed = script_to_events(script).unwrap();
+            assert_eq!(
+                parsed.last().map(|event| event.elapse()),
+                events.last().map(|event| event.elapse()),
+                "{}",
+                script
+            );
+        }
+    }
+
+    #[test]
+    fn even_typing_replays_as_recorded() {
+        let events = timeline(vec![
+            press(Key::KeyA, 0),
+            release(Key::KeyA, 30),
+            press(Key::KeyB, 70),
+            release(Key::KeyB, 30),
+            press(Key::KeyC, 70),
+            release(Key::KeyC, 30),
+        ]);
+        let script = gestures_to_script(&compact(&events));
+        assert_eq!(script, "type_keys(\"abc\",30,100);\n");
+        assert_eq!(script_to_events(&script).unwrap(), events);
+    }
+}
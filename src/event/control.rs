@@ -0,0 +1,242 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use crate::common::{SimulateSignal, SIMULATE_STATE_CHANNEL};
+use crossbeam_channel::RecvTimeoutError;
+use lazy_static::lazy_static;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+///state of the running simulation, driven by signals of SIMULATE_STATE_CHANNEL
+static PAUSED: AtomicBool = AtomicBool::new(false);
+static STOPPED: AtomicBool = AtomicBool::new(false);
+///a simulation thread holds a Running guard
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+///time since the last reset with pauses left out
+struct Clock {
+    started: Instant,
+    paused: Duration,
+    paused_at: Option<Instant>,
+}
+
+impl Clock {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            paused: Duration::ZERO,
+            paused_at: None,
+        }
+    }
+    fn pause(&mut self) {
+        self.paused_at.get_or_insert_with(Instant::now);
+    }
+    fn resume(&mut self) {
+        if let Some(at) = self.paused_at.take() {
+            self.paused += at.elapsed();
+        }
+    }
+    fn active(&self) -> Duration {
+        let paused = self.paused + self.paused_at.map_or(Duration::ZERO, |at| at.elapsed());
+        self.started.elapsed().saturating_sub(paused)
+    }
+}
+
+lazy_static! {
+    static ref CLOCK: Mutex<Clock> = Mutex::new(Clock::new());
+}
+
+fn clock() -> MutexGuard<'static, Clock> {
+    CLOCK.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+///time the run has been going, time spent paused does not count
+pub fn active_time() -> Duration {
+    clock().active()
+}
+
+///marks the simulation thread alive until dropped, also when the thread unwinds
+pub struct Running;
+
+pub fn running() -> Running {
+    RUNNING.store(true, Ordering::SeqCst);
+    Running
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+///forget signals left by a previous run
+pub fn reset() {
+    while SIMULATE_STATE_CHANNEL.1.try_recv().is_ok() {}
+    PAUSED.store(false, Ordering::SeqCst);
+    STOPPED.store(false, Ordering::SeqCst);
+    *clock() = Clock::new();
+}
+
+pub fn is_stopped() -> bool {
+    STOPPED.load(Ordering::SeqCst)
+}
+
+fn apply(signal: SimulateSignal) {
+    match signal {
+        SimulateSignal::Stop => {
+            info!("simulating should be stopped");
+            STOPPED.store(true, Ordering::SeqCst);
+            PAUSED.store(false, Ordering::SeqCst);
+            clock().resume();
+        }
+        SimulateSignal::Pause => {
+            info!("simulating paused");
+            PAUSED.store(true, Ordering::SeqCst);
+            clock().pause();
+        }
+        SimulateSignal::Resume => {
+            info!("simulating resumed");
+            PAUSED.store(false, Ordering::SeqCst);
+            clock().resume();
+        }
+    }
+}
+
+///apply pending signals and block while paused, returns true if stop was requested
+pub fn poll() -> bool {
+    loop {
+        if is_stopped() {
+            return true;
+        }
+        let signal = if PAUSED.load(Ordering::SeqCst) {
+            match SIMULATE_STATE_CHANNEL.1.recv() {
+                Ok(signal) => signal,
+                Err(_) => return is_stopped(),
+            }
+        } else {
+            match SIMULATE_STATE_CHANNEL.1.try_recv() {
+                Ok(signal) => signal,
+                Err(_) => return false,
+            }
+        };
+        apply(signal);
+    }
+}
+
+///sleep that halts while paused and returns as soon as stop is requested,
+///the time left is kept and slept after resume, returns false if stopped
+pub fn sleep(duration: Duration) -> bool {
+    let mut remaining = duration;
+    while !remaining.is_zero() {
+        if poll() {
+            return false;
+        }
+        let start = Instant::now();
+        match SIMULATE_STATE_CHANNEL.1.recv_timeout(remaining) {
+            Ok(signal) => {
+                remaining = remaining.saturating_sub(start.elapsed());
+                apply(signal);
+            }
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => {
+                std::thread::sleep(remaining);
+                break;
+            }
+        }
+    }
+    !poll()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::simulate_state_send;
+    use crate::event::{backend::install_mock, Data, Iteration};
+    use std::thread::{sleep as wait, spawn};
+
+    fn is_paused() -> bool {
+        PAUSED.load(Ordering::SeqCst)
+    }
+
+    ///send signal once after millis
+    fn send_after(millis: u64, signal: SimulateSignal) -> std::thread::JoinHandle<()> {
+        spawn(move || {
+            wait(Duration::from_millis(millis));
+            simulate_state_send(signal);
+        })
+    }
+
+    #[test]
+    fn pause_keeps_the_remaining_delay() {
+        let (_guard, _) = install_mock();
+        reset();
+        let pause = send_after(200, SimulateSignal::Pause);
+        let resume = send_after(600, SimulateSignal::Resume);
+        let probe = spawn(|| {
+            wait(Duration::from_millis(400));
+            is_paused()
+        });
+        let start = Instant::now();
+        assert!(sleep(Duration::from_millis(400)));
+        let elapsed = start.elapsed();
+        pause.join().unwrap();
+        resume.join().unwrap();
+        assert!(probe.join().unwrap());
+        //200 before the pause, 400 paused, the 200 left after it, a restart would take 1000
+        assert!(
+            elapsed >= Duration::from_millis(780) && elapsed < Duration::from_millis(950),
+            "{:?}",
+            elapsed
+        );
+        assert!(!is_paused());
+        let active = active_time();
+        assert!(
+            active >= Duration::from_millis(380) && active < Duration::from_millis(550),
+            "{:?}",
+            active
+        );
+        reset();
+    }
+
+    #[test]
+    fn stop_interrupts_a_long_delay() {
+        let (_guard, _) = install_mock();
+        reset();
+        let stop = send_after(50, SimulateSignal::Stop);
+        let start = Instant::now();
+        let result = Data::new("wait".to_string(), "delay(60000);".to_string())
+            .simulate(&Iteration { index: 0, count: 1 });
+        let elapsed = start.elapsed();
+        stop.join().unwrap();
+        assert!(result.is_err());
+        assert!(elapsed < Duration::from_millis(200), "{:?}", elapsed);
+        assert!(poll());
+        reset();
+    }
+
+    #[test]
+    fn stop_ends_a_pause() {
+        let (_guard, _) = install_mock();
+        reset();
+        simulate_state_send(SimulateSignal::Pause);
+        let stop = send_after(50, SimulateSignal::Stop);
+        let start = Instant::now();
+        assert!(!sleep(Duration::from_millis(10)));
+        stop.join().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(200));
+        assert!(is_stopped() && !is_paused());
+        reset();
+        assert!(!poll());
+    }
+}
@@ -0,0 +1,222 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use super::{backend::backend, report::report_abort, SimulateError};
+use crate::common::{simulate_state_send, Float, SimulateSignal, UInt};
+use lazy_static::lazy_static;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+///presses of Escape closer together than this count as one burst
+const ESCAPE_WINDOW: Duration = Duration::from_millis(1500);
+
+///synthesized events in the current second, checked against the limit of the run
+struct Rate {
+    limit: UInt,
+    second: Instant,
+    count: UInt,
+}
+
+lazy_static! {
+    static ref RATE: Mutex<Rate> = Mutex::new(Rate {
+        limit: 0,
+        second: Instant::now(),
+        count: 0,
+    });
+    ///size of the main display, measured once a run instead of on every mouse move
+    static ref DISPLAY: Mutex<Option<(Float, Float)>> = Mutex::new(None);
+}
+
+///stop the running simulation the same way the stop hotkey does, and note why in its report
+pub fn abort(reason: &str) {
+    warn!("failsafe: {}", reason);
+    report_abort(reason);
+    simulate_state_send(SimulateSignal::Stop);
+}
+
+///max synthesized events per second for the next run, 0 for no limit
+pub fn set_event_limit(limit: UInt) {
+    let mut rate = RATE.lock().unwrap_or_else(|err| err.into_inner());
+    rate.limit = limit;
+    rate.second = Instant::now();
+    rate.count = 0;
+}
+
+impl Rate {
+    ///count one event sent at now, returns the limit once it is passed and lifts it
+    fn count(&mut self, now: Instant) -> Option<UInt> {
+        if self.limit == 0 {
+            return None;
+        }
+        if now.duration_since(self.second) >= Duration::from_secs(1) {
+            self.second = now;
+            self.count = 0;
+        }
+        self.count += 1;
+        if self.count <= self.limit {
+            return None;
+        }
+        let limit = self.limit;
+        self.limit = 0;
+        Some(limit)
+    }
+}
+
+///count one synthesized event, aborts the run once the limit is passed
+pub fn check_event_rate() -> Result<(), SimulateError> {
+    let passed = RATE
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .count(Instant::now());
+    match passed {
+        Some(limit) => {
+            abort(&format!("more than {} events in a second", limit));
+            Err(SimulateError::Stopped)
+        }
+        None => Ok(()),
+    }
+}
+
+///measure the display again on the next corner check, the resolution may have changed
+pub fn forget_display_size() {
+    *DISPLAY.lock().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+fn display_size() -> (Float, Float) {
+    let mut display = DISPLAY.lock().unwrap_or_else(|err| err.into_inner());
+    *display.get_or_insert_with(|| backend().display_size().unwrap_or((Float::MAX, Float::MAX)))
+}
+
+///whether x, y is within margin pixels of a corner of the main display
+pub fn in_corner(x: Float, y: Float, margin: Float) -> bool {
+    let (width, height) = display_size();
+    let horizontal = x <= margin || x >= width - 1 as Float - margin;
+    let vertical = y <= margin || y >= height - 1 as Float - margin;
+    horizontal && vertical
+}
+
+///counts Escape presses that follow each other quickly
+#[derive(Debug, Default)]
+pub struct EscapeCounter {
+    presses: Vec<Instant>,
+}
+
+impl EscapeCounter {
+    ///returns true once `needed` presses happened within ESCAPE_WINDOW of each other
+    pub fn press(&mut self, needed: UInt) -> bool {
+        self.press_at(needed, Instant::now())
+    }
+    fn press_at(&mut self, needed: UInt, now: Instant) -> bool {
+        if let Some(last) = self.presses.last() {
+            if now.duration_since(*last) > ESCAPE_WINDOW {
+                self.presses.clear();
+            }
+        }
+        self.presses.push(now);
+        if needed > 0 && self.presses.len() as UInt >= needed {
+            self.presses.clear();
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{backend::install_mock, control::reset};
+
+    fn millis(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn corners_of_the_display() {
+        let (_guard, mock) = install_mock();
+        mock.set_display_size(Some((1920.0, 1080.0)));
+        forget_display_size();
+        assert!(in_corner(0.0, 0.0, 2.0));
+        assert!(in_corner(1919.0, 1079.0, 2.0));
+        assert!(in_corner(2.0, 1077.0, 2.0));
+        assert!(!in_corner(3.0, 0.0, 2.0));
+        assert!(!in_corner(960.0, 0.0, 2.0));
+        assert!(!in_corner(0.0, 540.0, 2.0));
+        //the size is measured once until forgotten
+        mock.set_display_size(Some((800.0, 600.0)));
+        assert!(!in_corner(799.0, 599.0, 0.0));
+        forget_display_size();
+        assert!(in_corner(799.0, 599.0, 0.0));
+        //an unknown display has no far corners
+        mock.set_display_size(None);
+        forget_display_size();
+        assert!(in_corner(0.0, 0.0, 0.0));
+        assert!(!in_corner(1919.0, 1079.0, 0.0));
+        forget_display_size();
+    }
+
+    #[test]
+    fn escape_presses_close_together() {
+        let start = Instant::now();
+        let mut escapes = EscapeCounter::default();
+        assert!(!escapes.press_at(3, start));
+        assert!(!escapes.press_at(3, start + millis(1000)));
+        assert!(escapes.press_at(3, start + millis(2500)));
+        //the burst is used up
+        assert!(!escapes.press_at(3, start + millis(2600)));
+        assert!(!escapes.press_at(0, start + millis(2700)));
+    }
+
+    #[test]
+    fn escape_window_expires() {
+        let start = Instant::now();
+        let mut escapes = EscapeCounter::default();
+        assert!(!escapes.press_at(3, start));
+        assert!(!escapes.press_at(3, start + millis(100)));
+        //too long after the last press, the count starts over
+        assert!(!escapes.press_at(3, start + millis(1601)));
+        assert!(!escapes.press_at(3, start + millis(1700)));
+        assert!(escapes.press_at(3, start + millis(1800)));
+    }
+
+    #[test]
+    fn rate_is_counted_per_second() {
+        let start = Instant::now();
+        let mut rate = Rate {
+            limit: 2,
+            second: start,
+            count: 0,
+        };
+        assert_eq!(rate.count(start), None);
+        assert_eq!(rate.count(start + millis(500)), None);
+        //a new second starts a new count
+        assert_eq!(rate.count(start + millis(1000)), None);
+        assert_eq!(rate.count(start + millis(1100)), None);
+        assert_eq!(rate.count(start + millis(1200)), Some(2));
+        //the limit is lifted once passed
+        assert_eq!(rate.count(start + millis(1300)), None);
+        let mut unlimited = Rate {
+            limit: 0,
+            second: start,
+            count: 0,
+        };
+        assert!((0..1000).all(|_| unlimited.count(start).is_none()));
+    }
+
+    #[test]
+    fn passing_the_rate_stops_the_run() {
+        let (_guard, _) = install_mock();
+        reset();
+        set_event_limit(2);
+        assert!(check_event_rate().is_ok());
+        assert!(check_event_rate().is_ok());
+        assert!(matches!(check_event_rate(), Err(SimulateError::Stopped)));
+        assert!(crate::event::control::poll());
+        set_event_limit(0);
+        assert!(check_event_rate().is_ok());
+        reset();
+    }
+}
@@ -0,0 +1,278 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use super::{events_to_data, script_to_events, Data, Event, Fidelity, ParseError, Simplify};
+use crate::common::{Int, UInt};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+///bump when the document layout changes, older versions must stay readable
+pub const FORMAT_VERSION: UInt = 1;
+
+///structured form of Data, events instead of rhai source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MacroDocument {
+    pub version: UInt,
+    pub name: String,
+    pub resolution: (Int, Int),
+    pub os: String,
+    pub os_version: String,
+    pub created_at: UInt,
+    pub updated_at: UInt,
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MacroFormat {
+    Json,
+    Ron,
+}
+
+impl MacroFormat {
+    ///guess by file extension, `.ron` is ron and everything else is json
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("ron") => MacroFormat::Ron,
+            _ => MacroFormat::Json,
+        }
+    }
+}
+
+impl MacroDocument {
+    pub fn from_data(data: &Data) -> Result<Self, FormatError> {
+        Ok(Self {
+            version: FORMAT_VERSION,
+            name: data.name.clone(),
+            resolution: data.resolution,
+            os: data.os.clone(),
+            os_version: data.os_version.clone(),
+            created_at: data.created_at,
+            updated_at: data.updated_at,
+            events: script_to_events(&data.content)?,
+        })
+    }
+    ///the rhai form, not stored in the library yet so id is None,
+    ///raw so that exporting it again gives back the same events
+    pub fn to_data(&self) -> Data {
+        let mut data = events_to_data(&self.events, Fidelity::Raw, &Simplify::default());
+        data.name = self.name.clone();
+        data.resolution = self.resolution;
+        data.os = self.os.clone();
+        data.os_version = self.os_version.clone();
+        data.created_at = self.created_at;
+        data.updated_at = self.updated_at;
+        data
+    }
+    pub fn to_text(&self, format: MacroFormat) -> Result<String, FormatError> {
+        match format {
+            MacroFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            MacroFormat::Ron => Ok(ron::ser::to_string_pretty(self, PrettyConfig::default())?),
+        }
+    }
+    pub fn from_text(text: &str, format: MacroFormat) -> Result<Self, FormatError> {
+        let document: Self = match format {
+            MacroFormat::Json => serde_json::from_str(text)?,
+            MacroFormat::Ron => ron::from_str(text)?,
+        };
+        if document.version > FORMAT_VERSION {
+            return Err(FormatError::UnsupportedVersion(document.version));
+        }
+        Ok(document)
+    }
+}
+
+pub fn export_data(data: &Data, format: MacroFormat) -> Result<String, FormatError> {
+    MacroDocument::from_data(data)?.to_text(format)
+}
+
+pub fn import_data(text: &str, format: MacroFormat) -> Result<Data, FormatError> {
+    Ok(MacroDocument::from_text(text, format)?.to_data())
+}
+
+pub fn export_file<P: AsRef<Path>>(data: &Data, path: P) -> Result<(), FormatError> {
+    let path = path.as_ref();
+    let text = export_data(data, MacroFormat::from_path(path))?;
+    fs::write(path, text)?;
+    Ok(())
+}
+
+pub fn import_file<P: AsRef<Path>>(path: P) -> Result<Data, FormatError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    import_data(&text, MacroFormat::from_path(path))
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Json(serde_json::Error),
+    Ron(ron::Error),
+    RonSpanned(ron::error::SpannedError),
+    Parse(ParseError),
+    Io(std::io::Error),
+    UnsupportedVersion(UInt),
+}
+
+impl From<serde_json::Error> for FormatError {
+    fn from(err: serde_json::Error) -> Self {
+        FormatError::Json(err)
+    }
+}
+
+impl From<ron::Error> for FormatError {
+    fn from(err: ron::Error) -> Self {
+        FormatError::Ron(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for FormatError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        FormatError::RonSpanned(err)
+    }
+}
+
+impl From<ParseError> for FormatError {
+    fn from(err: ParseError) -> Self {
+        FormatError::Parse(err)
+    }
+}
+
+impl From<std::io::Error> for FormatError {
+    fn from(err: std::io::Error) -> Self {
+        FormatError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{common::timeline, Button, Key};
+
+    ///one event of every kind, elapse is the running sum of durations as a script replays it
+    fn events() -> Vec<Event> {
+        timeline(vec![
+            Event::KeyPress {
+                key: Key::KeyA,
+                elapse: 0,
+                duration: 0,
+            },
+            Event::KeyRelease {
+                key: Key::Unknown(300),
+                elapse: 0,
+                duration: 40,
+            },
+            Event::MouseMove {
+                x: 10.0,
+                y: 20.5,
+                elapse: 0,
+                duration: 16,
+            },
+            Event::ButtonPress {
+                button: Button::Left,
+                x: 10.0,
+                y: 20.5,
+                elapse: 0,
+                duration: 100,
+            },
+            Event::Drag {
+                button: Button::Left,
+                x: 30.0,
+                y: 40.0,
+                elapse: 0,
+                duration: 16,
+            },
+            Event::ButtonRelease {
+                button: Button::Back,
+                x: 30.0,
+                y: 40.0,
+                elapse: 0,
+                duration: 80,
+            },
+            Event::Wheel {
+                x: -1,
+                y: 3,
+                elapse: 0,
+                duration: 200,
+            },
+        ])
+    }
+
+    fn document() -> MacroDocument {
+        MacroDocument {
+            version: FORMAT_VERSION,
+            name: "test".to_string(),
+            resolution: (1920, 1080),
+            os: "linux".to_string(),
+            os_version: "6".to_string(),
+            created_at: 1,
+            updated_at: 2,
+            events: events(),
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let text = document().to_text(MacroFormat::Json).unwrap();
+        assert_eq!(
+            MacroDocument::from_text(&text, MacroFormat::Json).unwrap(),
+            document()
+        );
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let text = document().to_text(MacroFormat::Ron).unwrap();
+        assert_eq!(
+            MacroDocument::from_text(&text, MacroFormat::Ron).unwrap(),
+            document()
+        );
+    }
+
+    #[test]
+    fn data_round_trip() {
+        let data = document().to_data();
+        assert_eq!(data.id, None);
+        for format in [MacroFormat::Json, MacroFormat::Ron] {
+            let imported = import_data(&export_data(&data, format).unwrap(), format).unwrap();
+            assert_eq!(imported.content, data.content);
+            assert_eq!(MacroDocument::from_data(&imported).unwrap(), document());
+        }
+    }
+
+    #[test]
+    fn newer_version_is_refused() {
+        let mut newer = document();
+        newer.version = FORMAT_VERSION + 1;
+        for format in [MacroFormat::Json, MacroFormat::Ron] {
+            let text = newer.to_text(format).unwrap();
+            assert!(matches!(
+                MacroDocument::from_text(&text, format),
+                Err(FormatError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+            ));
+        }
+    }
+
+    #[test]
+    fn older_version_is_read() {
+        let mut older = document();
+        older.version = FORMAT_VERSION - 1;
+        let text = older.to_text(MacroFormat::Json).unwrap();
+        assert_eq!(
+            MacroDocument::from_text(&text, MacroFormat::Json).unwrap(),
+            older
+        );
+    }
+
+    #[test]
+    fn format_by_extension() {
+        assert_eq!(MacroFormat::from_path(Path::new("a.ron")), MacroFormat::Ron);
+        assert_eq!(MacroFormat::from_path(Path::new("a.RON")), MacroFormat::Ron);
+        assert_eq!(
+            MacroFormat::from_path(Path::new("a.json")),
+            MacroFormat::Json
+        );
+        assert_eq!(MacroFormat::from_path(Path::new("a")), MacroFormat::Json);
+    }
+}
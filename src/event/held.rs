@@ -0,0 +1,151 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use super::{backend::backend, Button, Event, Key};
+use lazy_static::lazy_static;
+use std::sync::{Mutex, MutexGuard};
+
+///keys and buttons pressed by the simulation and not released yet
+#[derive(Default)]
+struct Held {
+    keys: Vec<Key>,
+    buttons: Vec<Button>,
+}
+
+lazy_static! {
+    static ref HELD: Mutex<Held> = Mutex::new(Held::default());
+}
+
+//a panicking simulate thread must not stop us from releasing
+fn held() -> MutexGuard<'static, Held> {
+    HELD.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+///remember a simulated event that pressed or released something
+pub fn track(event: &Event) {
+    let mut held = held();
+    match *event {
+        Event::KeyPress { key, .. } if !held.keys.contains(&key) => held.keys.push(key),
+        Event::KeyRelease { key, .. } => held.keys.retain(|held_key| *held_key != key),
+        Event::ButtonPress { button, .. } if !held.buttons.contains(&button) => {
+            held.buttons.push(button)
+        }
+        Event::ButtonRelease { button, .. } => {
+            held.buttons.retain(|held_button| *held_button != button)
+        }
+        _ => {}
+    }
+}
+
+///release everything still held, last pressed first, buttons at the current point
+pub fn release_all() {
+    let (keys, buttons) = {
+        let mut held = held();
+        (
+            std::mem::take(&mut held.keys),
+            std::mem::take(&mut held.buttons),
+        )
+    };
+    let backend = backend();
+    for key in keys.into_iter().rev() {
+        info!("releasing held key {:?}", key);
+        let event = Event::KeyRelease {
+            key,
+            elapse: 0,
+            duration: 0,
+        };
+        if let Err(err) = backend.simulate(event) {
+            warn!("release key {:?} error: {:?}", key, err);
+        }
+    }
+    let (x, y) = backend.current_point();
+    for button in buttons.into_iter().rev() {
+        info!("releasing held button {:?}", button);
+        let event = Event::ButtonRelease {
+            button,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        };
+        if let Err(err) = backend.simulate(event) {
+            warn!("release button {:?} error: {:?}", button, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{backend::install_mock, Data, Iteration};
+
+    #[test]
+    fn error_mid_script_releases_what_is_held() {
+        let (_guard, mock) = install_mock();
+        //other tests may leave something held
+        release_all();
+        mock.clear();
+        let result = Data::new(
+            "held".to_string(),
+            "key_press(Key::ShiftLeft);\nkey_press(Key::a);\nbutton_press(Button::Middle, 5, 6);\n\
+            key_release(Key::a);\nno_such_function();"
+                .to_string(),
+        )
+        .simulate(&Iteration { index: 0, count: 1 });
+        assert!(result.is_err());
+        mock.clear();
+        release_all();
+        assert_eq!(
+            mock.trace(),
+            vec![
+                Event::KeyRelease {
+                    key: Key::ShiftLeft,
+                    elapse: 0,
+                    duration: 0
+                },
+                Event::ButtonRelease {
+                    button: Button::Middle,
+                    x: 5.0,
+                    y: 6.0,
+                    elapse: 0,
+                    duration: 0
+                },
+            ]
+        );
+        //nothing is left to release twice
+        mock.clear();
+        release_all();
+        assert!(mock.trace().is_empty());
+    }
+
+    #[test]
+    fn last_pressed_is_released_first() {
+        let (_guard, mock) = install_mock();
+        release_all();
+        mock.clear();
+        for key in [Key::ControlLeft, Key::ShiftLeft, Key::KeyA] {
+            track(&Event::KeyPress {
+                key,
+                elapse: 0,
+                duration: 0,
+            });
+        }
+        track(&Event::KeyRelease {
+            key: Key::KeyA,
+            elapse: 0,
+            duration: 0,
+        });
+        release_all();
+        let released: Vec<Event> = [Key::ShiftLeft, Key::ControlLeft]
+            .into_iter()
+            .map(|key| Event::KeyRelease {
+                key,
+                elapse: 0,
+                duration: 0,
+            })
+            .collect();
+        assert_eq!(mock.trace(), released);
+    }
+}
@@ -13,7 +13,11 @@ use crate::{
     impls::{TraitInto, TraitReverseInto},
 };
 
-use super::{common::SimulateError, Button, Event, GrabError, Key, ListenError};
+use super::{
+    common::SimulateError, Button, Event, GrabError, Key, ListenError, NameError, BUTTON_NAMES,
+    KEY_ALIASES, KEY_NAMES,
+};
+use std::{fmt, str::FromStr};
 
 impl Into<Event> for _Event {
     fn into(self) -> Event {
@@ -360,12 +364,28 @@ impl Into<_Key> for Key {
     }
 }
 
+//codes rdev reports for the side buttons, XBUTTON1/2 on Windows, buttons 8/9 on X11
+#[cfg(target_os = "windows")]
+const BACK_CODE: u8 = 1;
+#[cfg(target_os = "windows")]
+const FORWARD_CODE: u8 = 2;
+#[cfg(target_os = "linux")]
+const BACK_CODE: u8 = 8;
+#[cfg(target_os = "linux")]
+const FORWARD_CODE: u8 = 9;
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+const BACK_CODE: u8 = 3;
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+const FORWARD_CODE: u8 = 4;
+
 impl Into<Button> for _Button {
     fn into(self) -> Button {
         match self {
             _Button::Left => Button::Left,
             _Button::Right => Button::Right,
             _Button::Middle => Button::Middle,
+            _Button::Unknown(BACK_CODE) => Button::Back,
+            _Button::Unknown(FORWARD_CODE) => Button::Forward,
             _Button::Unknown(number) => Button::Unknown(number),
         }
     }
@@ -377,7 +397,206 @@ impl Into<_Button> for Button {
             Button::Left => _Button::Left,
             Button::Right => _Button::Right,
             Button::Middle => _Button::Middle,
+            Button::Back => _Button::Unknown(BACK_CODE),
+            Button::Forward => _Button::Unknown(FORWARD_CODE),
             Button::Unknown(number) => _Button::Unknown(number),
         }
     }
 }
+
+impl fmt::Display for SimulateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimulateError::Default => write!(f, "could not simulate event"),
+            SimulateError::Stopped => write!(f, "simulation stopped"),
+            SimulateError::Event(event) => write!(f, "could not simulate {:?}", event),
+            SimulateError::Clipboard(message) => write!(f, "clipboard error: {}", message),
+            SimulateError::Rhai(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SimulateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SimulateError::Rhai(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GrabError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GrabError::EventTapError => write!(f, "could not create event tap"),
+            GrabError::LoopSourceError => write!(f, "could not create run loop source"),
+            GrabError::MissingDisplayError => write!(f, "no display found"),
+            GrabError::KeyboardError => write!(f, "could not open keyboard"),
+            GrabError::KeyHookError(code) => write!(f, "could not set keyboard hook: {}", code),
+            GrabError::MouseHookError(code) => write!(f, "could not set mouse hook: {}", code),
+            GrabError::SimulateError => write!(f, "could not simulate grabbed event"),
+            GrabError::IoError(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for GrabError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GrabError::IoError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ListenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ListenError::EventTapError => write!(f, "could not create event tap"),
+            ListenError::LoopSourceError => write!(f, "could not create run loop source"),
+            ListenError::MissingDisplayError => write!(f, "no display found"),
+            ListenError::KeyboardError => write!(f, "could not open keyboard"),
+            ListenError::RecordContextEnablingError => {
+                write!(f, "could not enable record context")
+            }
+            ListenError::RecordContextError => write!(f, "could not create record context"),
+            ListenError::XRecordExtensionError => write!(f, "XRecord extension is missing"),
+            ListenError::KeyHookError(code) => write!(f, "could not set keyboard hook: {}", code),
+            ListenError::MouseHookError(code) => write!(f, "could not set mouse hook: {}", code),
+        }
+    }
+}
+
+impl std::error::Error for ListenError {}
+
+///the number inside function(number), e.g. key_code(65)
+fn code_call<T: FromStr>(function: &str, text: &str) -> Option<T> {
+    text.strip_prefix(function)?
+        .trim()
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .trim()
+        .parse()
+        .ok()
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Key::Unknown(code) => write!(f, "key_code({})", code),
+            key => match KEY_NAMES.iter().find(|(_, named)| named == key) {
+                Some((name, _)) => write!(f, "{}", name),
+                None => write!(f, "{:?}", key),
+            },
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = NameError;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        KEY_NAMES
+            .iter()
+            .chain(KEY_ALIASES.iter())
+            .find(|(name, _)| *name == text)
+            .map(|(_, key)| *key)
+            .or_else(|| code_call("key_code", text).map(Key::Unknown))
+            .ok_or_else(|| NameError(text.to_string()))
+    }
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Button::Unknown(code) => write!(f, "button_code({})", code),
+            button => match BUTTON_NAMES.iter().find(|(_, named)| named == button) {
+                Some((name, _)) => write!(f, "{}", name),
+                None => write!(f, "{:?}", button),
+            },
+        }
+    }
+}
+
+impl FromStr for Button {
+    type Err = NameError;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        BUTTON_NAMES
+            .iter()
+            .find(|(name, _)| *name == text)
+            .map(|(_, button)| *button)
+            .or_else(|| code_call("button_code", text).map(Button::Unknown))
+            .ok_or_else(|| NameError(text.to_string()))
+    }
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown name: {}", self.0)
+    }
+}
+
+impl std::error::Error for NameError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn key_names_round_trip() {
+        for (name, key) in KEY_NAMES {
+            assert_eq!(key.to_string(), name);
+            assert_eq!(name.parse::<Key>(), Ok(key));
+        }
+    }
+
+    #[test]
+    fn key_aliases_parse_to_their_key() {
+        for (alias, key) in KEY_ALIASES {
+            assert_eq!(alias.parse::<Key>(), Ok(key));
+            assert_eq!(key.to_string().parse::<Key>(), Ok(key));
+        }
+    }
+
+    #[test]
+    fn button_names_round_trip() {
+        for (name, button) in BUTTON_NAMES {
+            assert_eq!(button.to_string(), name);
+            assert_eq!(name.parse::<Button>(), Ok(button));
+        }
+    }
+
+    #[test]
+    fn unknown_codes_round_trip() {
+        for code in [0, 1, 255, 65535, u32::MAX] {
+            let key = Key::Unknown(code);
+            assert_eq!(key.to_string(), format!("key_code({})", code));
+            assert_eq!(key.to_string().parse::<Key>(), Ok(key));
+        }
+        for code in [0, 7, u8::MAX] {
+            let button = Button::Unknown(code);
+            assert_eq!(button.to_string(), format!("button_code({})", code));
+            assert_eq!(button.to_string().parse::<Button>(), Ok(button));
+        }
+        assert_eq!("key_code( 12 )".parse::<Key>(), Ok(Key::Unknown(12)));
+        assert!("key_code(-1)".parse::<Key>().is_err());
+        assert!("button_code(256)".parse::<Button>().is_err());
+        assert!("KeyA".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let mut names = HashSet::new();
+        for (name, _) in KEY_NAMES.iter().chain(KEY_ALIASES.iter()) {
+            assert!(names.insert(*name), "key name {} twice", name);
+        }
+        for (index, (name, key)) in KEY_NAMES.iter().enumerate() {
+            let twice = KEY_NAMES[index + 1..].iter().any(|(_, other)| other == key);
+            assert!(!twice, "key of {} named twice", name);
+        }
+        let names: HashSet<&str> = BUTTON_NAMES.iter().map(|(name, _)| *name).collect();
+        let buttons: HashSet<Button> = BUTTON_NAMES.iter().map(|(_, button)| *button).collect();
+        assert_eq!(names.len(), BUTTON_NAMES.len());
+        assert_eq!(buttons.len(), BUTTON_NAMES.len());
+    }
+}
@@ -4,19 +4,56 @@ use crate::i;
 use log::{info, warn};
 
 use crate::{
-    common::{Float, Int, UInt},
+    common::{scale_millis, Float, Int, UInt},
     impls::TraitReverseInto,
 };
-use std::{thread::sleep, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+mod backend;
+mod chord;
 mod common;
+mod compact;
+mod control;
+mod failsafe;
+mod format;
+mod held;
 mod impls;
+mod parser;
+mod path;
+mod report;
 mod rhai;
+mod simplify;
+mod synthetic;
+pub use backend::{backend, set_backend, InputBackend, MockBackend, RdevBackend};
+#[cfg(test)]
+pub use backend::install_mock;
+pub use chord::{Chord, ChordError, KeyState, Modifiers};
 pub use common::{
-    current_point, events_to_data, grab, simulate_event, simulate_event_data, GrabError,
-    ListenError, SimulateError,
+    current_point, events_to_data, grab, simulate_event, simulate_event_data, simulate_predicate,
+    GrabError, Iteration, ListenError, SimulateError,
 };
+pub use compact::Fidelity;
+pub use control::{
+    active_time, is_running, poll as stop_requested, reset as reset_control, running,
+    sleep as sleep_unless_stopped, Running,
+};
+pub use failsafe::{abort, forget_display_size, in_corner, set_event_limit, EscapeCounter};
+pub use format::{
+    export_data, export_file, import_data, import_file, FormatError, MacroDocument, MacroFormat,
+    FORMAT_VERSION,
+};
+pub use held::release_all as release_held;
+pub use parser::{script_to_events, ParseError};
+pub use report::{
+    begin_report, finish_report, last_report, report_abort, report_panic, report_result, reports,
+    Failure, Report,
+};
+pub use rhai::{run_uncached, set_legacy_keys};
+pub use simplify::Simplify;
+pub use synthetic::is_synthetic;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum Event {
     KeyPress {
         key: Key,
@@ -102,6 +139,18 @@ impl Event {
         };
         self
     }
+    fn set_elapse(mut self, value: UInt) -> Self {
+        match self {
+            Event::KeyPress { ref mut elapse, .. } => *elapse = value,
+            Event::KeyRelease { ref mut elapse, .. } => *elapse = value,
+            Event::ButtonPress { ref mut elapse, .. } => *elapse = value,
+            Event::ButtonRelease { ref mut elapse, .. } => *elapse = value,
+            Event::MouseMove { ref mut elapse, .. } => *elapse = value,
+            Event::Drag { ref mut elapse, .. } => *elapse = value,
+            Event::Wheel { ref mut elapse, .. } => *elapse = value,
+        };
+        self
+    }
     fn elapse(&self) -> UInt {
         match self {
             Event::KeyPress { elapse, .. } => *elapse,
@@ -126,62 +175,65 @@ impl Event {
         }
     }
 
+    ///rhai statement of the event, the delay goes first since duration is the gap to the previous event
     pub fn to_string(&self) -> String {
         let delay_str: String = {
             let duration = self.duration();
-            let mut delay_str = String::from("\n");
+            let mut delay_str = String::new();
             if duration > 0 {
                 delay_str += &format!("delay({});\n", duration);
             }
             delay_str
         };
+        format!("{}{}", delay_str, self.call())
+    }
+
+    ///rhai statement of the event without the delay before it
+    pub fn call(&self) -> String {
         match self {
-            Event::KeyPress { key, .. } => {
-                format!("key_press({});{}", key.as_str(), delay_str)
-            }
-            Event::KeyRelease { key, .. } => {
-                format!("key_release({});{}", key.as_str(), delay_str)
-            }
+            Event::KeyPress { key, .. } => format!("key_press({});\n", key.script()),
+            Event::KeyRelease { key, .. } => format!("key_release({});\n", key.script()),
             Event::ButtonPress { button, x, y, .. } => match button {
-                Button::Left => format!("button_left_press({},{});{}", x, y, delay_str),
-                Button::Right => format!("button_right_press({},{});{}", x, y, delay_str),
-                Button::Middle => String::new(),
-                Button::Unknown(_) => String::new(),
+                Button::Left => format!("button_left_press({},{});\n", x, y),
+                Button::Right => format!("button_right_press({},{});\n", x, y),
+                button => format!("button_press({},{},{});\n", button.script(), x, y),
             },
             Event::ButtonRelease { button, x, y, .. } => match button {
-                Button::Left => format!("button_left_release({},{});{}", x, y, delay_str),
-                Button::Right => format!("button_right_release({},{});{}", x, y, delay_str),
-                Button::Middle => String::new(),
-                Button::Unknown(_) => String::new(),
+                Button::Left => format!("button_left_release({},{});\n", x, y),
+                Button::Right => format!("button_right_release({},{});\n", x, y),
+                button => format!("button_release({},{},{});\n", button.script(), x, y),
             },
-            Event::MouseMove { x, y, .. } => format!("mouse_move({},{});{}", x, y, delay_str),
+            Event::MouseMove { x, y, .. } => format!("mouse_move({},{});\n", x, y),
             Event::Drag { button, x, y, .. } => match button {
-                Button::Left => format!("drag_left_instant({},{});{}", x, y, delay_str),
-                Button::Right => format!("drag_right_instant({},{});{}", x, y, delay_str),
-                Button::Middle => String::new(),
-                Button::Unknown(_) => String::new(),
+                Button::Left => format!("drag_left_instant({},{});\n", x, y),
+                Button::Right => format!("drag_right_instant({},{});\n", x, y),
+                button => format!("drag_instant({},{},{});\n", button.script(), x, y),
+            },
+            Event::Wheel { x, y, .. } => match (*x, *y) {
+                (0, 0) => String::new(),
+                (0, y) if y > 0 => format!("wheel_down({});\n", y),
+                (0, y) => format!("wheel_up({});\n", y.abs()),
+                (x, 0) if x > 0 => format!("wheel_right({});\n", x),
+                (x, 0) => format!("wheel_left({});\n", x.abs()),
+                (x, y) => format!("wheel({},{});\n", x, y),
             },
-            Event::Wheel { y, .. } => {
-                if *y > 0 {
-                    format!("wheel_down({});\ndelay({});", y, delay_str)
-                } else if *y < 0 {
-                    format!("wheel_up({});\ndelay({});", y.abs(), delay_str)
-                } else {
-                    String::new()
-                }
-            }
         }
     }
 
     fn simulate(&self) -> Result<(), SimulateError> {
-        sleep(Duration::from_millis(self.duration().into_std()));
+        //skip the rest of a gesture once stop is requested
+        if !control::sleep(Duration::from_millis(scale_millis(
+            self.duration().into_std(),
+        ))) {
+            return Err(SimulateError::Stopped);
+        }
         //info!("simulate: {:?}", self);
         let event = self.clone();
         simulate_event(event)
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Key {
     Alt,
     AltGr,
@@ -294,146 +346,222 @@ pub enum Key {
 }
 
 impl Key {
-    pub fn as_str(&self) -> &str {
+    ///the modifier used by common shortcuts, Meta(command) on macOS
+    #[cfg(target_os = "macos")]
+    pub fn primary_modifier() -> Key {
+        Key::MetaLeft
+    }
+    ///the modifier used by common shortcuts, Control on Windows and Linux
+    #[cfg(not(target_os = "macos"))]
+    pub fn primary_modifier() -> Key {
+        Key::ControlLeft
+    }
+    ///how generated scripts refer to the key
+    pub fn script(&self) -> String {
         match self {
-            Key::Alt => "Alt",
-            Key::AltGr => "AltGr",
-            Key::Backspace => "Backspace",
-            Key::CapsLock => "CapsLock",
-            Key::ControlLeft => "ControlLeft",
-            Key::ControlRight => "ControlRight",
-            Key::Delete => "Delete",
-            Key::DownArrow => "DownArrow",
-            Key::End => "End",
-            Key::Escape => "Escape",
-            Key::F1 => "f1",
-            Key::F10 => "f10",
-            Key::F11 => "f11",
-            Key::F12 => "f12",
-            Key::F2 => "f2",
-            Key::F3 => "f3",
-            Key::F4 => "f4",
-            Key::F5 => "f5",
-            Key::F6 => "f6",
-            Key::F7 => "f7",
-            Key::F8 => "f8",
-            Key::F9 => "f9",
-            Key::Home => "Home",
-            Key::LeftArrow => "LeftArrow",
-            Key::MetaLeft => "MetaLeft",
-            Key::MetaRight => "MetaRight",
-            Key::PageDown => "PageDown",
-            Key::PageUp => "PageUp",
-            Key::Return => "Return",
-            Key::RightArrow => "RightArrow",
-            Key::ShiftLeft => "ShiftLeft",
-            Key::ShiftRight => "ShiftRight",
-            Key::Space => "Space",
-            Key::Tab => "Tab",
-            Key::UpArrow => "UpArrow",
-            Key::PrintScreen => "PrintScreen",
-            Key::ScrollLock => "ScrollLock",
-            Key::Pause => "Pause",
-            Key::NumLock => "NumLock",
-            Key::BackQuote => "BackQuote",
-            Key::Num1 => "n1",
-            Key::Num2 => "n",
-            Key::Num3 => "n3",
-            Key::Num4 => "n4",
-            Key::Num5 => "n5",
-            Key::Num6 => "n6",
-            Key::Num7 => "n7",
-            Key::Num8 => "n8",
-            Key::Num9 => "n9",
-            Key::Num0 => "n0",
-            Key::Minus => "Minus",
-            Key::Equal => "Equal",
-            Key::KeyQ => "q",
-            Key::KeyW => "w",
-            Key::KeyE => "e",
-            Key::KeyR => "r",
-            Key::KeyT => "t",
-            Key::KeyY => "y",
-            Key::KeyU => "u",
-            Key::KeyI => "i",
-            Key::KeyO => "o",
-            Key::KeyP => "p",
-            Key::LeftBracket => "LeftBracket",
-            Key::RightBracket => "RightBracket",
-            Key::KeyA => "a",
-            Key::KeyS => "s",
-            Key::KeyD => "d",
-            Key::KeyF => "f",
-            Key::KeyG => "g",
-            Key::KeyH => "h",
-            Key::KeyJ => "j",
-            Key::KeyK => "k",
-            Key::KeyL => "l",
-            Key::SemiColon => "SemiColon",
-            Key::Quote => "Quote",
-            Key::BackSlash => "BackSlash",
-            Key::IntlBackslash => "IntlBackslash",
-            Key::KeyZ => "z",
-            Key::KeyX => "x",
-            Key::KeyC => "c",
-            Key::KeyV => "v",
-            Key::KeyB => "b",
-            Key::KeyN => "n",
-            Key::KeyM => "m",
-            Key::Comma => "Comma",
-            Key::Dot => "Dot",
-            Key::Slash => "Slash",
-            Key::Insert => "Insert",
-            Key::KpReturn => "KpReturn",
-            Key::KpMinus => "KpMinus",
-            Key::KpPlus => "KpPlus",
-            Key::KpMultiply => "KpMultiply",
-            Key::KpDivide => "KpDivide",
-            Key::Kp0 => "Kp0",
-            Key::Kp1 => "Kp1",
-            Key::Kp2 => "Kp2",
-            Key::Kp3 => "Kp3",
-            Key::Kp4 => "Kp4",
-            Key::Kp5 => "Kp5",
-            Key::Kp6 => "Kp6",
-            Key::Kp7 => "Kp7",
-            Key::Kp8 => "Kp8",
-            Key::Kp9 => "Kp9",
-            Key::KpDelete => "KpDelete",
-            Key::Function => "Function",
-            Key::Unknown(_) => "Unknown",
+            Key::Unknown(_) => self.to_string(),
+            key => format!("Key::{}", key),
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+///script name of every key, Display and FromStr read this table so recorded scripts parse back,
+///Unknown(code) is written as key_code(code)
+pub const KEY_NAMES: [(&str, Key); 105] = [
+    ("Alt", Key::Alt),
+    ("AltGr", Key::AltGr),
+    ("Backspace", Key::Backspace),
+    ("CapsLock", Key::CapsLock),
+    ("ControlLeft", Key::ControlLeft),
+    ("ControlRight", Key::ControlRight),
+    ("Delete", Key::Delete),
+    ("DownArrow", Key::DownArrow),
+    ("End", Key::End),
+    ("Escape", Key::Escape),
+    ("F1", Key::F1),
+    ("F10", Key::F10),
+    ("F11", Key::F11),
+    ("F12", Key::F12),
+    ("F2", Key::F2),
+    ("F3", Key::F3),
+    ("F4", Key::F4),
+    ("F5", Key::F5),
+    ("F6", Key::F6),
+    ("F7", Key::F7),
+    ("F8", Key::F8),
+    ("F9", Key::F9),
+    ("Home", Key::Home),
+    ("LeftArrow", Key::LeftArrow),
+    ("MetaLeft", Key::MetaLeft),
+    ("MetaRight", Key::MetaRight),
+    ("PageDown", Key::PageDown),
+    ("PageUp", Key::PageUp),
+    ("Return", Key::Return),
+    ("RightArrow", Key::RightArrow),
+    ("ShiftLeft", Key::ShiftLeft),
+    ("ShiftRight", Key::ShiftRight),
+    ("Space", Key::Space),
+    ("Tab", Key::Tab),
+    ("UpArrow", Key::UpArrow),
+    ("PrintScreen", Key::PrintScreen),
+    ("ScrollLock", Key::ScrollLock),
+    ("Pause", Key::Pause),
+    ("NumLock", Key::NumLock),
+    ("BackQuote", Key::BackQuote),
+    ("n1", Key::Num1),
+    ("n2", Key::Num2),
+    ("n3", Key::Num3),
+    ("n4", Key::Num4),
+    ("n5", Key::Num5),
+    ("n6", Key::Num6),
+    ("n7", Key::Num7),
+    ("n8", Key::Num8),
+    ("n9", Key::Num9),
+    ("n0", Key::Num0),
+    ("Minus", Key::Minus),
+    ("Equal", Key::Equal),
+    ("q", Key::KeyQ),
+    ("w", Key::KeyW),
+    ("e", Key::KeyE),
+    ("r", Key::KeyR),
+    ("t", Key::KeyT),
+    ("y", Key::KeyY),
+    ("u", Key::KeyU),
+    ("i", Key::KeyI),
+    ("o", Key::KeyO),
+    ("p", Key::KeyP),
+    ("LeftBracket", Key::LeftBracket),
+    ("RightBracket", Key::RightBracket),
+    ("a", Key::KeyA),
+    ("s", Key::KeyS),
+    ("d", Key::KeyD),
+    ("f", Key::KeyF),
+    ("g", Key::KeyG),
+    ("h", Key::KeyH),
+    ("j", Key::KeyJ),
+    ("k", Key::KeyK),
+    ("l", Key::KeyL),
+    ("SemiColon", Key::SemiColon),
+    ("Quote", Key::Quote),
+    ("BackSlash", Key::BackSlash),
+    ("IntlBackslash", Key::IntlBackslash),
+    ("z", Key::KeyZ),
+    ("x", Key::KeyX),
+    ("c", Key::KeyC),
+    ("v", Key::KeyV),
+    ("b", Key::KeyB),
+    ("n", Key::KeyN),
+    ("m", Key::KeyM),
+    ("Comma", Key::Comma),
+    ("Dot", Key::Dot),
+    ("Slash", Key::Slash),
+    ("Insert", Key::Insert),
+    ("KpReturn", Key::KpReturn),
+    ("KpMinus", Key::KpMinus),
+    ("KpPlus", Key::KpPlus),
+    ("KpMultiply", Key::KpMultiply),
+    ("KpDivide", Key::KpDivide),
+    ("Kp0", Key::Kp0),
+    ("Kp1", Key::Kp1),
+    ("Kp2", Key::Kp2),
+    ("Kp3", Key::Kp3),
+    ("Kp4", Key::Kp4),
+    ("Kp5", Key::Kp5),
+    ("Kp6", Key::Kp6),
+    ("Kp7", Key::Kp7),
+    ("Kp8", Key::Kp8),
+    ("Kp9", Key::Kp9),
+    ("KpDelete", Key::KpDelete),
+    ("Function", Key::Function),
+];
+///names that older scripts used
+pub const KEY_ALIASES: [(&str, Key); 12] = [
+    ("f1", Key::F1),
+    ("f10", Key::F10),
+    ("f11", Key::F11),
+    ("f12", Key::F12),
+    ("f2", Key::F2),
+    ("f3", Key::F3),
+    ("f4", Key::F4),
+    ("f5", Key::F5),
+    ("f6", Key::F6),
+    ("f7", Key::F7),
+    ("f8", Key::F8),
+    ("f9", Key::F9),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Button {
     Left,
     Right,
     Middle,
+    ///side button, the platform reports it as an unknown code
+    Back,
+    ///side button, the platform reports it as an unknown code
+    Forward,
     Unknown(u8),
 }
 
+impl Button {
+    ///how generated scripts refer to the button
+    pub fn script(&self) -> String {
+        match self {
+            Button::Unknown(_) => self.to_string(),
+            button => format!("Button::{}", button),
+        }
+    }
+}
+
+///script name of every button, Unknown(code) is written as button_code(code)
+pub const BUTTON_NAMES: [(&str, Button); 5] = [
+    ("Left", Button::Left),
+    ("Right", Button::Right),
+    ("Middle", Button::Middle),
+    ("Back", Button::Back),
+    ("Forward", Button::Forward),
+];
+
+///text that is neither a key or button name nor a key_code/button_code call
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameError(pub String);
+
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub struct Data {
-    _name: String,
-    _resolution: (Int, Int),
-    _os: String,
-    _os_version: String,
+    ///row id in the macro library, None until stored
+    pub id: Option<Int>,
+    pub name: String,
+    pub resolution: (Int, Int),
+    pub os: String,
+    pub os_version: String,
     pub content: String,
+    pub created_at: UInt,
+    pub updated_at: UInt,
 }
 impl Data {
-    pub fn new(_name: String, content: String) -> Self {
+    pub fn new(name: String, content: String) -> Self {
         Self {
-            _name,
-            _resolution: (0, 0),
-            _os: String::new(),
-            _os_version: String::new(),
+            id: None,
+            name,
+            resolution: (0, 0),
+            os: std::env::consts::OS.to_string(),
+            os_version: String::new(),
             content,
+            created_at: 0,
+            updated_at: 0,
         }
     }
-    pub fn simulate(&self) -> Result<(), SimulateError> {
-        simulate_event_data(self)
+    pub fn simulate(&self, iteration: &Iteration) -> Result<(), SimulateError> {
+        simulate_event_data(self, iteration)
+    }
+    ///millis from the first statement to the last event at 1x, None if content is not a generated script
+    pub fn timeline_millis(&self) -> Option<UInt> {
+        match script_to_events(&self.content) {
+            Ok(events) => Some(events.last().map_or(0, |event| event.elapse())),
+            Err(err) => {
+                warn!("timeline of {} unknown: {:?}", self.name, err);
+                None
+            }
+        }
     }
 }
@@ -0,0 +1,593 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use super::{compact::typed_key, Button, Event, Key};
+use crate::common::{Float, Int, UInt};
+
+///read a generated macro script back into events,
+///elapse is the sum of the delays before the event and duration is the delay right before it,
+///delays after the last event are dropped
+pub fn script_to_events(content: &str) -> Result<Vec<Event>, ParseError> {
+    let mut events = Vec::new();
+    let mut elapse: UInt = 0;
+    let mut pending: UInt = 0;
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index as UInt + 1;
+        let line = match line.find("//") {
+            Some(position) => &line[..position],
+            None => line,
+        };
+        for statement in line.split(';') {
+            let statement = statement.trim();
+            if statement.is_empty() {
+                continue;
+            }
+            let (name, args) = split_call(statement, line_number)?;
+            if name == "delay" {
+                let duration = number_args::<1>(&args, line_number)?[0];
+                if duration < 0 as Float {
+                    return Err(ParseError::InvalidArgument {
+                        line: line_number,
+                        argument: args[0].to_string(),
+                    });
+                }
+                pending += duration as UInt;
+                elapse += duration as UInt;
+                continue;
+            }
+            let built = match build_gesture(name, &args, line_number)? {
+                Some(built) => built,
+                None => vec![build_event(name, &args, line_number)?],
+            };
+            //the first event also waits for the delays written before the call
+            for (index, event) in built.into_iter().enumerate() {
+                let duration = match index {
+                    0 => pending + event.duration(),
+                    _ => event.duration(),
+                };
+                elapse += event.duration();
+                events.push(event.set_elapse(elapse).set_duration(duration));
+            }
+            pending = 0;
+        }
+    }
+    Ok(events)
+}
+
+fn split_call(statement: &str, line: UInt) -> Result<(&str, Vec<&str>), ParseError> {
+    let syntax_error = || ParseError::Syntax {
+        line,
+        statement: statement.to_string(),
+    };
+    let open = statement.find('(').ok_or_else(syntax_error)?;
+    if !statement.ends_with(')') {
+        return Err(syntax_error());
+    }
+    let name = statement[..open].trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(syntax_error());
+    }
+    let inner = statement[open + 1..statement.len() - 1].trim();
+    let args = if inner.is_empty() {
+        Vec::new()
+    } else {
+        inner.split(',').map(|arg| arg.trim()).collect()
+    };
+    Ok((name, args))
+}
+
+fn number_args<const N: usize>(args: &[&str], line: UInt) -> Result<[Float; N], ParseError> {
+    if args.len() != N {
+        return Err(ParseError::ArgumentCount {
+            line,
+            expected: N as UInt,
+            found: args.len() as UInt,
+        });
+    }
+    let mut numbers = [0 as Float; N];
+    for (index, arg) in args.iter().enumerate() {
+        numbers[index] = arg
+            .parse::<Float>()
+            .map_err(|_| ParseError::InvalidArgument {
+                line,
+                argument: arg.to_string(),
+            })?;
+    }
+    Ok(numbers)
+}
+
+fn key_arg(args: &[&str], line: UInt) -> Result<Key, ParseError> {
+    if args.len() != 1 {
+        return Err(ParseError::ArgumentCount {
+            line,
+            expected: 1,
+            found: args.len() as UInt,
+        });
+    }
+    key_name(args[0], line)
+}
+
+fn key_name(arg: &str, line: UInt) -> Result<Key, ParseError> {
+    let name = arg.strip_prefix("Key::").unwrap_or(arg);
+    name.parse().map_err(|_| ParseError::UnknownKey {
+        line,
+        name: arg.to_string(),
+    })
+}
+
+fn button_name(arg: &str, line: UInt) -> Result<Button, ParseError> {
+    let name = arg.strip_prefix("Button::").unwrap_or(arg);
+    name.parse().map_err(|_| ParseError::InvalidArgument {
+        line,
+        argument: arg.to_string(),
+    })
+}
+
+fn count_between(args: &[&str], min: usize, max: usize, line: UInt) -> Result<(), ParseError> {
+    if args.len() < min || args.len() > max {
+        return Err(ParseError::ArgumentCount {
+            line,
+            expected: max as UInt,
+            found: args.len() as UInt,
+        });
+    }
+    Ok(())
+}
+
+///calls of a compacted recording, they stand for several events,
+///None if name is not one of them
+fn build_gesture(name: &str, args: &[&str], line: UInt) -> Result<Option<Vec<Event>>, ParseError> {
+    let key = |key: Key, press: bool, duration: UInt| -> Event {
+        match press {
+            true => Event::KeyPress {
+                key,
+                elapse: 0,
+                duration,
+            },
+            false => Event::KeyRelease {
+                key,
+                elapse: 0,
+                duration,
+            },
+        }
+    };
+    let click = |button: Button, x: Float, y: Float, hold: UInt| -> [Event; 2] {
+        [
+            Event::ButtonPress {
+                button,
+                x,
+                y,
+                elapse: 0,
+                duration: 0,
+            },
+            Event::ButtonRelease {
+                button,
+                x,
+                y,
+                elapse: 0,
+                duration: hold,
+            },
+        ]
+    };
+    let dragged = |button: Button, x: Float, y: Float, duration: UInt| -> Event {
+        Event::Drag {
+            button,
+            x,
+            y,
+            elapse: 0,
+            duration,
+        }
+    };
+    let events = match name {
+        //the glide of a simplified recording, its duration is spent moving
+        "mouse_move" if args.len() == 3 => {
+            let [x, y, duration] = number_args::<3>(args, line)?;
+            vec![Event::MouseMove {
+                x,
+                y,
+                elapse: 0,
+                duration: duration as UInt,
+            }]
+        }
+        //hold defaults to the 10 millis key_click and click_left wait without one
+        "key_click" => {
+            count_between(args, 1, 2, line)?;
+            let hold = match args.len() {
+                2 => number_args::<1>(&args[1..], line)?[0] as UInt,
+                _ => 10,
+            };
+            let pressed = key_name(args[0], line)?;
+            vec![key(pressed, true, 0), key(pressed, false, hold)]
+        }
+        "click_left" | "click_right" => {
+            count_between(args, 2, 3, line)?;
+            let button = match name {
+                "click_left" => Button::Left,
+                _ => Button::Right,
+            };
+            let [x, y] = number_args::<2>(&args[..2], line)?;
+            let hold = match args.len() {
+                3 => number_args::<1>(&args[2..], line)?[0] as UInt,
+                _ => 10,
+            };
+            click(button, x, y, hold).to_vec()
+        }
+        "click" => {
+            count_between(args, 3, 4, line)?;
+            let button = button_name(args[0], line)?;
+            let [x, y] = number_args::<2>(&args[1..3], line)?;
+            let hold = match args.len() {
+                4 => number_args::<1>(&args[3..], line)?[0] as UInt,
+                _ => 10,
+            };
+            click(button, x, y, hold).to_vec()
+        }
+        "double_click" => {
+            count_between(args, 5, 5, line)?;
+            let button = button_name(args[0], line)?;
+            let [x, y, hold, gap] = number_args::<4>(&args[1..], line)?;
+            let [first_press, first_release] = click(button, x, y, hold as UInt);
+            let [second_press, second_release] = click(button, x, y, hold as UInt);
+            vec![
+                first_press,
+                first_release,
+                second_press.set_duration(gap as UInt),
+                second_release,
+            ]
+        }
+        "drag_left" | "drag_right" | "drag" => {
+            let (button, numbers) = match name {
+                "drag_left" => (Button::Left, args),
+                "drag_right" => (Button::Right, args),
+                _ => {
+                    count_between(args, 6, 6, line)?;
+                    (button_name(args[0], line)?, &args[1..])
+                }
+            };
+            count_between(numbers, 5, 5, line)?;
+            let [from_x, from_y, to_x, to_y, duration] = number_args::<5>(numbers, line)?;
+            vec![
+                dragged(button, from_x, from_y, 0),
+                dragged(button, to_x, to_y, duration as UInt),
+            ]
+        }
+        "type_keys" => {
+            count_between(args, 3, 3, line)?;
+            let text = args[0]
+                .strip_prefix('"')
+                .and_then(|text| text.strip_suffix('"'))
+                .ok_or_else(|| ParseError::InvalidArgument {
+                    line,
+                    argument: args[0].to_string(),
+                })?;
+            let [hold, interval] = number_args::<2>(&args[1..], line)?;
+            let (hold, interval) = (hold as UInt, interval as UInt);
+            let mut events = Vec::new();
+            for (index, character) in text.chars().enumerate() {
+                let typed = typed_key(character).ok_or_else(|| ParseError::InvalidArgument {
+                    line,
+                    argument: character.to_string(),
+                })?;
+                let wait = match index {
+                    0 => 0,
+                    _ => interval.saturating_sub(hold),
+                };
+                events.push(key(typed, true, wait));
+                events.push(key(typed, false, hold));
+            }
+            events
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(events))
+}
+
+///first argument of button_press(Button::Middle, x, y) and the like, the rest are coordinates
+fn button_args(args: &[&str], line: UInt) -> Result<(Button, [Float; 2]), ParseError> {
+    if args.len() != 3 {
+        return Err(ParseError::ArgumentCount {
+            line,
+            expected: 3,
+            found: args.len() as UInt,
+        });
+    }
+    let button = button_name(args[0], line)?;
+    Ok((button, number_args::<2>(&args[1..], line)?))
+}
+
+fn build_event(name: &str, args: &[&str], line: UInt) -> Result<Event, ParseError> {
+    let pressed = |button: Button, x: Float, y: Float, press: bool| -> Event {
+        match press {
+            true => Event::ButtonPress {
+                button,
+                x,
+                y,
+                elapse: 0,
+                duration: 0,
+            },
+            false => Event::ButtonRelease {
+                button,
+                x,
+                y,
+                elapse: 0,
+                duration: 0,
+            },
+        }
+    };
+    let button_event = |button: Button, press: bool| -> Result<Event, ParseError> {
+        let [x, y] = number_args::<2>(args, line)?;
+        Ok(pressed(button, x, y, press))
+    };
+    let dragged = |button: Button, x: Float, y: Float| -> Event {
+        Event::Drag {
+            button,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        }
+    };
+    let drag_event = |button: Button| -> Result<Event, ParseError> {
+        let [x, y] = number_args::<2>(args, line)?;
+        Ok(dragged(button, x, y))
+    };
+    let wheel = |x: Int, y: Int| -> Event {
+        Event::Wheel {
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        }
+    };
+    //one axis, the sign comes from the function name
+    let wheel_event = |sign_x: Int, sign_y: Int| -> Result<Event, ParseError> {
+        let [delta] = number_args::<1>(args, line)?;
+        let delta = (delta as Int).abs();
+        Ok(wheel(sign_x * delta, sign_y * delta))
+    };
+    match name {
+        "key_press" => Ok(Event::KeyPress {
+            key: key_arg(args, line)?,
+            elapse: 0,
+            duration: 0,
+        }),
+        "key_release" => Ok(Event::KeyRelease {
+            key: key_arg(args, line)?,
+            elapse: 0,
+            duration: 0,
+        }),
+        "button_left_press" => button_event(Button::Left, true),
+        "button_left_release" => button_event(Button::Left, false),
+        "button_right_press" => button_event(Button::Right, true),
+        "button_right_release" => button_event(Button::Right, false),
+        "button_press" | "button_release" => {
+            let (button, [x, y]) = button_args(args, line)?;
+            Ok(pressed(button, x, y, name == "button_press"))
+        }
+        "mouse_move" => {
+            let [x, y] = number_args::<2>(args, line)?;
+            Ok(Event::MouseMove {
+                x,
+                y,
+                elapse: 0,
+                duration: 0,
+            })
+        }
+        "drag_left_instant" => drag_event(Button::Left),
+        "drag_right_instant" => drag_event(Button::Right),
+        "drag_instant" => {
+            let (button, [x, y]) = button_args(args, line)?;
+            Ok(dragged(button, x, y))
+        }
+        "wheel_down" => wheel_event(0, 1),
+        "wheel_up" => wheel_event(0, -1),
+        "wheel_right" => wheel_event(1, 0),
+        "wheel_left" => wheel_event(-1, 0),
+        "wheel" => {
+            let [x, y] = number_args::<2>(args, line)?;
+            Ok(wheel(x as Int, y as Int))
+        }
+        _ => Err(ParseError::UnknownFunction {
+            line,
+            name: name.to_string(),
+        }),
+    }
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum ParseError {
+    Syntax {
+        line: UInt,
+        statement: String,
+    },
+    UnknownFunction {
+        line: UInt,
+        name: String,
+    },
+    UnknownKey {
+        line: UInt,
+        name: String,
+    },
+    ArgumentCount {
+        line: UInt,
+        expected: UInt,
+        found: UInt,
+    },
+    InvalidArgument {
+        line: UInt,
+        argument: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{common::timeline, events_to_data, Fidelity, Simplify};
+
+    fn key_press(key: Key, duration: UInt) -> Event {
+        Event::KeyPress {
+            key,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    fn key_release(key: Key, duration: UInt) -> Event {
+        Event::KeyRelease {
+            key,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    fn button_press(button: Button, duration: UInt) -> Event {
+        Event::ButtonPress {
+            button,
+            x: 10.0,
+            y: 20.5,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    fn button_release(button: Button, duration: UInt) -> Event {
+        Event::ButtonRelease {
+            button,
+            x: 10.0,
+            y: 20.5,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    fn drag(button: Button, duration: UInt) -> Event {
+        Event::Drag {
+            button,
+            x: -5.0,
+            y: 300.25,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    fn wheel(x: Int, y: Int, duration: UInt) -> Event {
+        Event::Wheel {
+            x,
+            y,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    fn round_trip(events: &Vec<Event>) -> Vec<Event> {
+        let data = events_to_data(events, Fidelity::Raw, &Simplify::default());
+        script_to_events(&data.content).unwrap()
+    }
+
+    #[test]
+    fn every_event_round_trips() {
+        let mut events = vec![
+            key_press(Key::KeyA, 0),
+            key_release(Key::KeyA, 40),
+            key_press(Key::F12, 10),
+            key_release(Key::F12, 0),
+            key_press(Key::Unknown(300), 5),
+            key_release(Key::Unknown(300), 5),
+            Event::MouseMove {
+                x: 10.0,
+                y: 20.5,
+                elapse: 0,
+                duration: 16,
+            },
+        ];
+        for button in [
+            Button::Left,
+            Button::Right,
+            Button::Middle,
+            Button::Back,
+            Button::Forward,
+            Button::Unknown(9),
+        ] {
+            events.push(button_press(button, 100));
+            events.push(button_release(button, 80));
+        }
+        for button in [
+            Button::Left,
+            Button::Right,
+            Button::Middle,
+            Button::Unknown(9),
+        ] {
+            events.push(drag(button, 16));
+        }
+        events.push(wheel(0, 3, 200));
+        events.push(wheel(0, -2, 0));
+        events.push(wheel(4, 0, 10));
+        events.push(wheel(-1, 0, 10));
+        events.push(wheel(-1, 3, 10));
+        let events = timeline(events);
+        assert_eq!(round_trip(&events), events);
+    }
+
+    #[test]
+    fn wheel_sign_comes_from_the_name() {
+        let events = script_to_events(
+            "wheel_down(2);\nwheel_up(2);\nwheel_right(3);\nwheel_left(3);\nwheel(-1,4);\n",
+        )
+        .unwrap();
+        let deltas: Vec<(Int, Int)> = events
+            .iter()
+            .map(|event| match *event {
+                Event::Wheel { x, y, .. } => (x, y),
+                _ => panic!("{:?} is not a wheel", event),
+            })
+            .collect();
+        assert_eq!(deltas, vec![(0, 2), (0, -2), (3, 0), (-3, 0), (-1, 4)]);
+    }
+
+    #[test]
+    fn key_prefix_is_optional() {
+        let prefixed = script_to_events("key_press(Key::a);\nkey_release(Key::F1);\n").unwrap();
+        let legacy = script_to_events("key_press(a);\nkey_release(F1);\n").unwrap();
+        assert_eq!(prefixed, legacy);
+        assert_eq!(
+            prefixed,
+            vec![key_press(Key::KeyA, 0), key_release(Key::F1, 0)]
+        );
+        let unknown = script_to_events("key_press(key_code(300));\n").unwrap();
+        assert_eq!(unknown, vec![key_press(Key::Unknown(300), 0)]);
+    }
+
+    #[test]
+    fn delays_add_up() {
+        let events =
+            script_to_events("delay(100);\nkey_press(Key::a);\ndelay(20);delay(30);\nkey_release(Key::a);\ndelay(500);\n")
+                .unwrap();
+        assert_eq!(
+            events,
+            timeline(vec![key_press(Key::KeyA, 100), key_release(Key::KeyA, 50)])
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert!(matches!(
+            script_to_events("key_press(Key::a);\nkey_press(Key::Nope);\n"),
+            Err(ParseError::UnknownKey { line: 2, .. })
+        ));
+        assert!(matches!(
+            script_to_events("\nfly(1);\n"),
+            Err(ParseError::UnknownFunction { line: 2, .. })
+        ));
+        assert!(matches!(
+            script_to_events("mouse_move(1);\n"),
+            Err(ParseError::ArgumentCount { line: 1, .. })
+        ));
+        assert!(matches!(
+            script_to_events("delay(-1);\n"),
+            Err(ParseError::InvalidArgument { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,261 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use crate::{
+    common::{Float, UInt},
+    random::random_float,
+};
+use lazy_static::lazy_static;
+use std::sync::RwLock;
+
+///shape of the path between two points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    Line,
+    ///cubic bezier, control points pushed sideways by at most `bend` * distance
+    Bezier { bend: Float },
+}
+
+///how progress along the path is spread over the duration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+///how many points a path is cut into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    Points(UInt),
+    ///one point every n millis of the duration
+    Step(UInt),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathStyle {
+    pub curve: Curve,
+    pub easing: Easing,
+    pub sampling: Sampling,
+    ///max pixels an inner point may be pushed off the curve
+    pub jitter: Float,
+}
+
+impl Default for PathStyle {
+    fn default() -> Self {
+        Self {
+            curve: Curve::Line,
+            easing: Easing::Linear,
+            sampling: Sampling::Step(10),
+            jitter: 0.0,
+        }
+    }
+}
+
+impl Easing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Self::Linear),
+            "ease_in" => Some(Self::EaseIn),
+            "ease_out" => Some(Self::EaseOut),
+            "ease_in_out" => Some(Self::EaseInOut),
+            _ => None,
+        }
+    }
+    fn apply(&self, t: Float) -> Float {
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t * t,
+            Self::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Self::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+impl Sampling {
+    fn count(&self, duration: UInt) -> UInt {
+        match *self {
+            Self::Points(count) => count.max(1),
+            Self::Step(step) => (duration / step.max(1)).max(1),
+        }
+    }
+}
+
+lazy_static! {
+    static ref PATH_STYLE: RwLock<PathStyle> = RwLock::new(PathStyle::default());
+}
+
+pub fn path_style() -> PathStyle {
+    *PATH_STYLE.read().unwrap_or_else(|err| err.into_inner())
+}
+
+pub fn set_path_style(style: PathStyle) {
+    *PATH_STYLE.write().unwrap_or_else(|err| err.into_inner()) = style;
+}
+
+///points after `from` up to and including `to`, each with the millis to wait before it;
+///the waits add up to `duration` and the last point is always exactly `to`
+pub fn path(
+    from: (Float, Float),
+    to: (Float, Float),
+    duration: UInt,
+    style: &PathStyle,
+) -> Vec<(Float, Float, UInt)> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let distance = (dx * dx + dy * dy).sqrt();
+    if distance == 0.0 {
+        return vec![(to.0, to.1, duration)];
+    }
+    //unit normal, used to bend the curve and push jitter sideways
+    let normal = (-dy / distance, dx / distance);
+    let controls = match style.curve {
+        Curve::Line => None,
+        Curve::Bezier { bend } => {
+            let bend = bend.abs() * distance;
+            let first = random_float(-bend, bend);
+            let second = random_float(-bend, bend);
+            Some((
+                (
+                    from.0 + dx / 3.0 + normal.0 * first,
+                    from.1 + dy / 3.0 + normal.1 * first,
+                ),
+                (
+                    from.0 + dx * 2.0 / 3.0 + normal.0 * second,
+                    from.1 + dy * 2.0 / 3.0 + normal.1 * second,
+                ),
+            ))
+        }
+    };
+    let count = style.sampling.count(duration);
+    let mut points = Vec::with_capacity(count as usize);
+    let mut waited = 0;
+    for index in 1..=count {
+        let t = style.easing.apply(index as Float / count as Float);
+        let (mut x, mut y) = match controls {
+            None => (from.0 + dx * t, from.1 + dy * t),
+            Some((c1, c2)) => {
+                let u = 1.0 - t;
+                let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                (
+                    a * from.0 + b * c1.0 + c * c2.0 + d * to.0,
+                    a * from.1 + b * c1.1 + c * c2.1 + d * to.1,
+                )
+            }
+        };
+        if index == count {
+            (x, y) = to;
+        } else if style.jitter > 0.0 {
+            let offset = random_float(-style.jitter, style.jitter);
+            x += normal.0 * offset;
+            y += normal.1 * offset;
+        }
+        //spread the remainder so the waits add up to the duration exactly
+        let until = (duration as u64 * index as u64 / count as u64) as UInt;
+        points.push((x, y, until - waited));
+        waited = until;
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::backend::install_mock;
+
+    fn styles() -> Vec<PathStyle> {
+        let mut styles = vec![];
+        for curve in [Curve::Line, Curve::Bezier { bend: 0.3 }] {
+            for easing in [
+                Easing::Linear,
+                Easing::EaseIn,
+                Easing::EaseOut,
+                Easing::EaseInOut,
+            ] {
+                for sampling in [Sampling::Points(1), Sampling::Points(7), Sampling::Step(10)] {
+                    for jitter in [0.0, 5.0] {
+                        styles.push(PathStyle {
+                            curve,
+                            easing,
+                            sampling,
+                            jitter,
+                        });
+                    }
+                }
+            }
+        }
+        styles
+    }
+
+    #[test]
+    fn ends_exactly_at_the_target() {
+        //bezier and jitter draw from the shared generator
+        let (_guard, _) = install_mock();
+        for style in styles() {
+            for (from, to) in [
+                ((0.0, 0.0), (100.0, 50.0)),
+                ((10.5, 20.25), (-3.75, 7.125)),
+                ((5.0, 5.0), (5.0, 5.0)),
+            ] {
+                let points = path(from, to, 123, &style);
+                let last = points.last().unwrap();
+                assert_eq!((last.0, last.1), to, "{:?}", style);
+            }
+        }
+    }
+
+    #[test]
+    fn waits_add_up_to_the_duration() {
+        let (_guard, _) = install_mock();
+        for style in styles() {
+            for duration in [0, 1, 9, 10, 123, 1000, 1001] {
+                let points = path((0.0, 0.0), (40.0, 30.0), duration, &style);
+                let waited: UInt = points.iter().map(|point| point.2).sum();
+                assert_eq!(waited, duration, "{:?}", style);
+                assert_eq!(points.len() as UInt, style.sampling.count(duration));
+            }
+        }
+    }
+
+    #[test]
+    fn straight_moves_stay_on_their_axis() {
+        let style = PathStyle {
+            sampling: Sampling::Points(10),
+            ..PathStyle::default()
+        };
+        let points = path((10.0, 20.0), (110.0, 20.0), 100, &style);
+        assert!(points.iter().all(|point| point.1 == 20.0));
+        assert!(points.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        let points = path((10.0, 20.0), (10.0, -80.0), 100, &style);
+        assert!(points.iter().all(|point| point.0 == 10.0));
+        assert!(points.windows(2).all(|pair| pair[0].1 > pair[1].1));
+        assert_eq!(
+            points.iter().map(|point| point.2).collect::<Vec<_>>(),
+            [10; 10]
+        );
+    }
+
+    #[test]
+    fn bends_and_jitter_only_push_sideways() {
+        let (_guard, _) = install_mock();
+        let style = PathStyle {
+            curve: Curve::Bezier { bend: 0.5 },
+            easing: Easing::Linear,
+            sampling: Sampling::Points(10),
+            jitter: 3.0,
+        };
+        let points = path((0.0, 0.0), (100.0, 0.0), 100, &style);
+        for (index, point) in points.iter().enumerate() {
+            assert!((point.0 - 10.0 * (index + 1) as Float).abs() < 1e-9);
+        }
+        assert_eq!(points[9].1, 0.0);
+    }
+}
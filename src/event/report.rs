@@ -0,0 +1,265 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use super::{rhai::CallFailure, SimulateError};
+use crate::common::{timestamp, UInt};
+use lazy_static::lazy_static;
+use rhai::EvalAltResult;
+use std::{fmt, sync::RwLock};
+
+///how many finished runs are kept for querying
+const MAX_REPORTS: usize = 20;
+
+///one failure of a run, position is where the script called the failing function
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub iteration: UInt,
+    pub function: Option<String>,
+    pub line: Option<UInt>,
+    pub column: Option<UInt>,
+    pub message: String,
+}
+
+impl Failure {
+    ///None if the error only means the run was stopped
+    pub fn from_error(iteration: UInt, err: &SimulateError) -> Option<Self> {
+        match err {
+            SimulateError::Stopped => None,
+            SimulateError::Rhai(err) => Self::from_rhai(iteration, err),
+            err => Some(Self {
+                iteration,
+                function: None,
+                line: None,
+                column: None,
+                message: err.to_string(),
+            }),
+        }
+    }
+    fn from_rhai(iteration: UInt, err: &EvalAltResult) -> Option<Self> {
+        let position = err.position();
+        let (function, message) = match err {
+            EvalAltResult::ErrorTerminated(..) => return None,
+            EvalAltResult::ErrorRuntime(value, _) => {
+                match value.clone().try_cast::<CallFailure>() {
+                    Some(failure) => (Some(failure.function), failure.message),
+                    None => (None, err.to_string()),
+                }
+            }
+            EvalAltResult::ErrorInFunctionCall(name, _, inner, _) => {
+                let inner = Self::from_rhai(iteration, inner)?;
+                (inner.function.or(Some(name.clone())), inner.message)
+            }
+            _ => (None, err.to_string()),
+        };
+        Some(Self {
+            iteration,
+            function,
+            line: position.line().map(|line| line as UInt),
+            column: position.position().map(|column| column as UInt),
+            message,
+        })
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "iteration {}", self.iteration)?;
+        if let Some(line) = self.line {
+            write!(f, ", line {}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        if let Some(ref function) = self.function {
+            write!(f, ", {}()", function)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+///everything that happened while a macro was simulated
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub name: String,
+    ///seed of the run, replaying with it gives the same random values
+    pub seed: u64,
+    pub started_at: UInt,
+    pub finished_at: Option<UInt>,
+    pub iterations: UInt,
+    pub stopped: bool,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    pub fn is_running(&self) -> bool {
+        self.finished_at.is_none()
+    }
+    ///seconds the run took so far
+    pub fn seconds(&self) -> UInt {
+        self.finished_at
+            .unwrap_or_else(timestamp)
+            .saturating_sub(self.started_at)
+    }
+}
+
+lazy_static! {
+    static ref REPORTS: RwLock<Vec<Report>> = RwLock::new(Vec::new());
+}
+
+pub fn begin_report(name: &str, seed: u64) {
+    let mut reports = REPORTS.write().unwrap();
+    if reports.len() >= MAX_REPORTS {
+        reports.remove(0);
+    }
+    reports.push(Report {
+        name: name.to_string(),
+        seed,
+        started_at: timestamp(),
+        finished_at: None,
+        iterations: 0,
+        stopped: false,
+        failures: Vec::new(),
+    });
+}
+
+///record the result of iteration, counted from 1
+pub fn report_result(iteration: UInt, result: &Result<(), SimulateError>) {
+    let mut reports = REPORTS.write().unwrap();
+    if let Some(report) = reports.last_mut() {
+        report.iterations = iteration;
+        if let Err(err) = result {
+            match Failure::from_error(iteration, err) {
+                Some(failure) => {
+                    warn!("simulate failed, {}", failure);
+                    report.failures.push(failure);
+                }
+                None => report.stopped = true,
+            }
+        }
+    }
+}
+
+pub fn report_panic(iteration: UInt) {
+    let mut reports = REPORTS.write().unwrap();
+    if let Some(report) = reports.last_mut() {
+        report.failures.push(Failure {
+            iteration,
+            function: None,
+            line: None,
+            column: None,
+            message: "simulate thread panicked".to_string(),
+        });
+    }
+}
+
+///a failsafe stopped the run
+pub fn report_abort(reason: &str) {
+    let mut reports = REPORTS.write().unwrap();
+    if let Some(report) = reports.last_mut() {
+        report.stopped = true;
+        report.failures.push(Failure {
+            iteration: report.iterations,
+            function: None,
+            line: None,
+            column: None,
+            message: format!("aborted, {}", reason),
+        });
+    }
+}
+
+pub fn finish_report() {
+    if let Some(report) = REPORTS.write().unwrap().last_mut() {
+        report.finished_at = Some(timestamp());
+    }
+}
+
+pub fn last_report() -> Option<Report> {
+    REPORTS.read().unwrap().last().cloned()
+}
+
+///finished and running reports, oldest first
+pub fn reports() -> Vec<Report> {
+    REPORTS.read().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{backend::install_mock, Data, Iteration};
+
+    fn failure(content: &str) -> Option<Failure> {
+        let err = Data::new("test".to_string(), content.to_string())
+            .simulate(&Iteration { index: 1, count: 2 })
+            .unwrap_err();
+        Failure::from_error(2, &err)
+    }
+
+    #[test]
+    fn failed_call_has_its_position_and_function() {
+        let (_guard, mock) = install_mock();
+        mock.set_refuse(true);
+        let failure = failure("let x = 1;\n\n  key_click(Key::a);").unwrap();
+        mock.set_refuse(false);
+        assert_eq!(failure.iteration, 2);
+        assert_eq!(failure.function.as_deref(), Some("key_click"));
+        assert_eq!((failure.line, failure.column), (Some(3), Some(3)));
+        assert!(failure.message.contains("KeyPress"), "{}", failure.message);
+        assert_eq!(
+            failure.to_string(),
+            format!("iteration 2, line 3:3, key_click(): {}", failure.message)
+        );
+    }
+
+    #[test]
+    fn failure_in_a_script_function_points_at_its_call() {
+        let (_guard, mock) = install_mock();
+        mock.set_refuse(true);
+        let failure = failure("fn tap() {\n    key_press(Key::a);\n}\ntap();").unwrap();
+        mock.set_refuse(false);
+        assert_eq!(failure.function.as_deref(), Some("key_press"));
+        assert_eq!((failure.line, failure.column), (Some(4), Some(1)));
+    }
+
+    #[test]
+    fn script_errors_have_a_position() {
+        let (_guard, _) = install_mock();
+        let thrown = failure("let x = 1;\nthrow \"boom\";").unwrap();
+        assert_eq!(thrown.function, None);
+        assert_eq!((thrown.line, thrown.column), (Some(2), Some(1)));
+        assert!(thrown.message.contains("boom"));
+        let unknown = failure("key_press(no_such_key);").unwrap();
+        assert_eq!((unknown.line, unknown.column), (Some(1), Some(11)));
+        assert!(Failure::from_error(1, &SimulateError::Stopped).is_none());
+    }
+
+    #[test]
+    fn reports_keep_the_latest_runs() {
+        let (_guard, mock) = install_mock();
+        for run in 0..MAX_REPORTS + 2 {
+            begin_report(&format!("run {}", run), run as u64);
+            finish_report();
+        }
+        begin_report("last", 7);
+        report_result(1, &Ok(()));
+        mock.set_refuse(true);
+        let err = Data::new("last".to_string(), "key_click(Key::a);".to_string())
+            .simulate(&Iteration { index: 1, count: 3 })
+            .unwrap_err();
+        mock.set_refuse(false);
+        report_result(2, &Err(err));
+        report_result(3, &Err(SimulateError::Stopped));
+        assert!(last_report().unwrap().is_running());
+        finish_report();
+        let reports = reports();
+        assert_eq!(reports.len(), MAX_REPORTS);
+        assert_eq!(reports[0].name, "run 3");
+        let last = reports.last().unwrap();
+        assert_eq!((last.name.as_str(), last.seed), ("last", 7));
+        assert_eq!(last.iterations, 3);
+        assert!(last.stopped && !last.is_running());
+        assert_eq!(last.failures.len(), 1);
+        assert_eq!(last.failures[0].iteration, 2);
+    }
+}
@@ -3,16 +3,59 @@ use crate::i;
 #[allow(unused_imports)]
 use log::{info, warn};
 
-use super::{common::virtual_path, current_point, Button, Event, Key};
+use super::{
+    compact::typed_key,
+    control, current_point,
+    path::{path, path_style, set_path_style, Curve, Easing, PathStyle, Sampling},
+    Button, Event, Iteration, Key, BUTTON_NAMES, KEY_ALIASES, KEY_NAMES,
+};
 use crate::{
-    common::{Float, Int, UInt, SIMULATE_STATE_CHANNEL},
+    common::{scale_millis, Float, Int, UInt},
     event::SimulateError,
+    random::{jitter_millis, random_float, random_int},
 };
 use arboard::Clipboard;
-use rhai::{Engine, Scope};
-use std::time::Duration;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Module, Position, Scope, AST};
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+type RhaiResult<T = ()> = Result<T, Box<EvalAltResult>>;
 
-fn drag<T, F, I, G, Q>(from_point: (T, F), to_point: (I, G), duration: Q, button: Button)
+///what failed inside a script function, carried by EvalAltResult::ErrorRuntime
+#[derive(Debug, Clone)]
+pub struct CallFailure {
+    pub function: String,
+    pub message: String,
+}
+
+fn fail(function: &str, err: SimulateError) -> Box<EvalAltResult> {
+    match err {
+        SimulateError::Stopped => EvalAltResult::ErrorTerminated("stop".into(), Position::NONE),
+        err => EvalAltResult::ErrorRuntime(
+            Dynamic::from(CallFailure {
+                function: function.to_string(),
+                message: err.to_string(),
+            }),
+            Position::NONE,
+        ),
+    }
+    .into()
+}
+
+fn simulate(function: &str, event: Event) -> RhaiResult {
+    event.simulate().map_err(|err| fail(function, err))
+}
+
+fn drag<T, F, I, G, Q>(
+    function: &str,
+    from_point: (T, F),
+    to_point: (I, G),
+    duration: Q,
+    button: Button,
+) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
@@ -20,97 +63,104 @@ where
     G: Into<Float>,
     Q: Into<Float>,
 {
-    let duration = duration.into() as u128;
-    if let Some(points) = virtual_path(
-        from_point.0,
-        from_point.1,
-        to_point.0,
-        to_point.1,
-        duration as UInt,
-    ) {
-        points.iter().for_each(move |point| {
-            let _ = Event::Drag {
+    let from_point = (from_point.0.into(), from_point.1.into());
+    let to_point = (to_point.0.into(), to_point.1.into());
+    let duration = duration.into() as UInt;
+    for (x, y, duration) in path(from_point, to_point, duration, &path_style()) {
+        simulate(
+            function,
+            Event::Drag {
                 button,
-                x: point.0 as Float,
-                y: point.1 as Float,
+                x,
+                y,
                 elapse: 0,
-                duration: point.2,
-            }
-            .simulate();
-        });
+                duration,
+            },
+        )?;
     }
+    Ok(())
 }
-fn click<T, F>(point: (T, F), button: Button)
+fn click<T, F>(function: &str, point: (T, F), button: Button, hold: UInt) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
 {
     let (x, y) = (point.0.into(), point.1.into());
-    let _ = Event::ButtonPress {
-        button,
-        x,
-        y,
-        elapse: 0,
-        duration: 0,
-    }
-    .simulate();
-    let _ = Event::ButtonRelease {
-        button,
-        x,
-        y,
-        elapse: 0,
-        duration: 10,
-    }
-    .simulate();
+    simulate(
+        function,
+        Event::ButtonPress {
+            button,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        },
+    )?;
+    simulate(
+        function,
+        Event::ButtonRelease {
+            button,
+            x,
+            y,
+            elapse: 0,
+            duration: hold,
+        },
+    )
 }
 
-fn wheel<T>(y: T)
-where
-    T: Into<Float>,
-{
-    let y = y.into() as Int;
-    let _ = Event::Wheel {
-        x: 0,
-        y,
-        elapse: 0,
-        duration: 0,
-    }
-    .simulate();
+fn wheel(function: &str, x: Int, y: Int) -> RhaiResult {
+    simulate(
+        function,
+        Event::Wheel {
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        },
+    )
 }
 
-pub fn drag_left_instant<T, F>(x: T, y: F)
+pub fn drag_left_instant<T, F>(x: T, y: F) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
 {
     let (x, y) = (x.into(), y.into());
-    let _ = Event::Drag {
-        button: Button::Left,
-        x,
-        y,
-        elapse: 0,
-        duration: 0,
-    }
-    .simulate();
+    simulate(
+        "drag_left_instant",
+        Event::Drag {
+            button: Button::Left,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        },
+    )
 }
 
-pub fn drag_right_instant<T, F>(x: T, y: F)
+pub fn drag_right_instant<T, F>(x: T, y: F) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
 {
     let (x, y) = (x.into(), y.into());
-    let _ = Event::Drag {
-        button: Button::Right,
-        x,
-        y,
-        elapse: 0,
-        duration: 0,
-    }
-    .simulate();
+    simulate(
+        "drag_right_instant",
+        Event::Drag {
+            button: Button::Right,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        },
+    )
 }
 
-pub fn drag_left_relative<T, F, I, G, Q>(from_point: (T, F), offset: (I, G), duration: Q)
+pub fn drag_left_relative<T, F, I, G, Q>(
+    from_point: (T, F),
+    offset: (I, G),
+    duration: Q,
+) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
@@ -124,9 +174,19 @@ where
         duration.into(),
     );
     let to_point = (from_point.0 + offset.0, from_point.1 + offset.1);
-    drag(from_point, to_point, duration, Button::Left);
+    drag(
+        "drag_left_relative",
+        from_point,
+        to_point,
+        duration,
+        Button::Left,
+    )
 }
-pub fn drag_right_relative<T, F, I, G, Q>(from_point: (T, F), offset: (I, G), duration: Q)
+pub fn drag_right_relative<T, F, I, G, Q>(
+    from_point: (T, F),
+    offset: (I, G),
+    duration: Q,
+) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
@@ -140,108 +200,429 @@ where
         duration.into(),
     );
     let to_point = (from_point.0 + offset.0, from_point.1 + offset.1);
-    drag(from_point, to_point, duration, Button::Right);
+    drag(
+        "drag_right_relative",
+        from_point,
+        to_point,
+        duration,
+        Button::Right,
+    )
+}
+pub fn click_left<T, F>(x: T, y: F) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+{
+    click("click_left", (x, y), Button::Left, 10)
+}
+pub fn click_right<T, F>(x: T, y: F) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+{
+    click("click_right", (x, y), Button::Right, 10)
+}
+pub fn button_left_click() -> RhaiResult {
+    click("button_left_click", current_point(), Button::Left, 10)
+}
+pub fn button_right_click() -> RhaiResult {
+    click("button_right_click", current_point(), Button::Right, 10)
+}
+pub fn click_left_hold<T, F, Q>(x: T, y: F, hold: Q) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+    Q: Into<Float>,
+{
+    click("click_left_hold", (x, y), Button::Left, hold.into() as UInt)
+}
+pub fn click_right_hold<T, F, Q>(x: T, y: F, hold: Q) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+    Q: Into<Float>,
+{
+    click(
+        "click_right_hold",
+        (x, y),
+        Button::Right,
+        hold.into() as UInt,
+    )
+}
+pub fn click_button_hold<T, F, Q>(button: Button, x: T, y: F, hold: Q) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+    Q: Into<Float>,
+{
+    click("click_button_hold", (x, y), button, hold.into() as UInt)
+}
+///two clicks gap millis apart, each held for hold millis
+pub fn double_click<T, F, Q, G>(button: Button, x: T, y: F, hold: Q, gap: G) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+    Q: Into<Float>,
+    G: Into<Float>,
+{
+    let (x, y, hold) = (x.into(), y.into(), hold.into() as UInt);
+    click("double_click", (x, y), button, hold)?;
+    wait("double_click", gap.into() as u64)?;
+    click("double_click", (x, y), button, hold)
 }
-pub fn click_left<T, F>(x: T, y: F)
+///press-free drag from one point to another over duration millis, as recorded drags are written
+fn drag_between<T, F, I, G, Q>(
+    function: &str,
+    button: Button,
+    from_point: (T, F),
+    to_point: (I, G),
+    duration: Q,
+) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
+    I: Into<Float>,
+    G: Into<Float>,
+    Q: Into<Float>,
 {
-    click((x, y), Button::Left);
+    let from_point = (from_point.0.into(), from_point.1.into());
+    simulate(
+        function,
+        Event::Drag {
+            button,
+            x: from_point.0,
+            y: from_point.1,
+            elapse: 0,
+            duration: 0,
+        },
+    )?;
+    drag(function, from_point, to_point, duration, button)
 }
-pub fn click_right<T, F>(x: T, y: F)
+pub fn drag_left<T, F, I, G, Q>(from_x: T, from_y: F, to_x: I, to_y: G, duration: Q) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
+    I: Into<Float>,
+    G: Into<Float>,
+    Q: Into<Float>,
 {
-    click((x, y), Button::Right);
+    drag_between(
+        "drag_left",
+        Button::Left,
+        (from_x, from_y),
+        (to_x, to_y),
+        duration,
+    )
 }
-pub fn button_left_click() {
-    click(current_point(), Button::Left);
+pub fn drag_right<T, F, I, G, Q>(from_x: T, from_y: F, to_x: I, to_y: G, duration: Q) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+    I: Into<Float>,
+    G: Into<Float>,
+    Q: Into<Float>,
+{
+    drag_between(
+        "drag_right",
+        Button::Right,
+        (from_x, from_y),
+        (to_x, to_y),
+        duration,
+    )
 }
-pub fn button_right_click() {
-    click(current_point(), Button::Right);
+pub fn drag_button<T, F, I, G, Q>(
+    button: Button,
+    from_x: T,
+    from_y: F,
+    to_x: I,
+    to_y: G,
+    duration: Q,
+) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+    I: Into<Float>,
+    G: Into<Float>,
+    Q: Into<Float>,
+{
+    drag_between(
+        "drag_button",
+        button,
+        (from_x, from_y),
+        (to_x, to_y),
+        duration,
+    )
 }
-pub fn button_left_press<T, F>(x: T, y: F)
+pub fn button_left_press<T, F>(x: T, y: F) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
 {
     let (x, y) = (x.into(), y.into());
-    let _ = Event::ButtonPress {
-        button: Button::Left,
-        x,
-        y,
-        elapse: 0,
-        duration: 0,
-    }
-    .simulate();
+    simulate(
+        "button_left_press",
+        Event::ButtonPress {
+            button: Button::Left,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        },
+    )
 }
-pub fn button_left_release<T, F>(x: T, y: F)
+pub fn button_left_release<T, F>(x: T, y: F) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
 {
     let (x, y) = (x.into(), y.into());
-    let _ = Event::ButtonRelease {
-        button: Button::Left,
-        x,
-        y,
-        elapse: 0,
-        duration: 0,
-    }
-    .simulate();
+    simulate(
+        "button_left_release",
+        Event::ButtonRelease {
+            button: Button::Left,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        },
+    )
 }
 
-pub fn button_right_press<T, F>(x: T, y: F)
+pub fn button_right_press<T, F>(x: T, y: F) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
 {
     let (x, y) = (x.into(), y.into());
-    let _ = Event::ButtonPress {
-        button: Button::Right,
-        x,
-        y,
-        elapse: 0,
-        duration: 0,
-    }
-    .simulate();
+    simulate(
+        "button_right_press",
+        Event::ButtonPress {
+            button: Button::Right,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        },
+    )
 }
 
-pub fn button_right_release<T, F>(x: T, y: F)
+pub fn button_right_release<T, F>(x: T, y: F) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
 {
     let (x, y) = (x.into(), y.into());
-    let _ = Event::ButtonRelease {
-        button: Button::Right,
-        x,
-        y,
-        elapse: 0,
-        duration: 0,
-    }
-    .simulate();
+    simulate(
+        "button_right_release",
+        Event::ButtonRelease {
+            button: Button::Right,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        },
+    )
+}
+
+///press or release any button, e.g. button_press(Button::Middle, x, y)
+pub fn button_press<T, F>(button: Button, x: T, y: F) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+{
+    let (x, y) = (x.into(), y.into());
+    simulate(
+        "button_press",
+        Event::ButtonPress {
+            button,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        },
+    )
+}
+pub fn button_release<T, F>(button: Button, x: T, y: F) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+{
+    let (x, y) = (x.into(), y.into());
+    simulate(
+        "button_release",
+        Event::ButtonRelease {
+            button,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        },
+    )
+}
+pub fn click_button<T, F>(button: Button, x: T, y: F) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+{
+    click("click_button", (x, y), button, 10)
+}
+pub fn button_click(button: Button) -> RhaiResult {
+    click("button_click", current_point(), button, 10)
+}
+pub fn drag_instant<T, F>(button: Button, x: T, y: F) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+{
+    let (x, y) = (x.into(), y.into());
+    simulate(
+        "drag_instant",
+        Event::Drag {
+            button,
+            x,
+            y,
+            elapse: 0,
+            duration: 0,
+        },
+    )
+}
+///drag with any button from x, y by offset_x, offset_y over duration millis
+pub fn drag_relative<T, F, I, G, Q>(
+    button: Button,
+    x: T,
+    y: F,
+    offset_x: I,
+    offset_y: G,
+    duration: Q,
+) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+    I: Into<Float>,
+    G: Into<Float>,
+    Q: Into<Float>,
+{
+    let from_point = (x.into(), y.into());
+    let to_point = (
+        from_point.0 + offset_x.into(),
+        from_point.1 + offset_y.into(),
+    );
+    drag("drag_relative", from_point, to_point, duration, button)
 }
 
-pub fn mouse_move<T, F>(x: T, y: F)
+pub fn mouse_move<T, F>(x: T, y: F) -> RhaiResult
 where
     T: Into<Float>,
     F: Into<Float>,
 {
     let x = x.into();
     let y = y.into();
-    let _ = Event::MouseMove {
-        x: x as Float,
-        y: y as Float,
-        elapse: 0,
-        duration: 0,
+    simulate(
+        "mouse_move",
+        Event::MouseMove {
+            x: x as Float,
+            y: y as Float,
+            elapse: 0,
+            duration: 0,
+        },
+    )
+}
+
+///glide from the current point to x, y over duration millis
+pub fn mouse_move_smooth<T, F, Q>(x: T, y: F, duration: Q) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+    Q: Into<Float>,
+{
+    let to_point = (x.into(), y.into());
+    let duration = duration.into() as UInt;
+    for (x, y, duration) in path(current_point(), to_point, duration, &path_style()) {
+        simulate(
+            "mouse_move_smooth",
+            Event::MouseMove {
+                x,
+                y,
+                elapse: 0,
+                duration,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn invalid_argument(function: &str, message: String) -> Box<EvalAltResult> {
+    EvalAltResult::ErrorRuntime(
+        Dynamic::from(CallFailure {
+            function: function.to_string(),
+            message,
+        }),
+        Position::NONE,
+    )
+    .into()
+}
+
+///"line" or "bezier"
+pub fn set_path_curve(name: &str) -> RhaiResult {
+    set_path_bend(name, 0.2)
+}
+///"bezier" with control points bent by at most `bend` * distance
+pub fn set_path_bend(name: &str, bend: Float) -> RhaiResult {
+    let curve = match name {
+        "line" => Curve::Line,
+        "bezier" => Curve::Bezier { bend },
+        _ => {
+            return Err(invalid_argument(
+                "set_path_bend",
+                format!("unknown curve {:?}", name),
+            ))
+        }
+    };
+    set_path_style(PathStyle {
+        curve,
+        ..path_style()
+    });
+    Ok(())
+}
+///"linear", "ease_in", "ease_out" or "ease_in_out"
+pub fn set_path_easing(name: &str) -> RhaiResult {
+    match Easing::from_name(name) {
+        Some(easing) => {
+            set_path_style(PathStyle {
+                easing,
+                ..path_style()
+            });
+            Ok(())
+        }
+        None => Err(invalid_argument(
+            "set_path_easing",
+            format!("unknown easing {:?}", name),
+        )),
     }
-    .simulate();
+}
+pub fn set_path_jitter<T>(jitter: T)
+where
+    T: Into<Float>,
+{
+    set_path_style(PathStyle {
+        jitter: jitter.into().max(0.0),
+        ..path_style()
+    });
+}
+pub fn set_path_points(count: Int) {
+    set_path_style(PathStyle {
+        sampling: Sampling::Points(count.max(1) as UInt),
+        ..path_style()
+    });
+}
+pub fn set_path_step(millis: Int) {
+    set_path_style(PathStyle {
+        sampling: Sampling::Step(millis.max(1) as UInt),
+        ..path_style()
+    });
 }
 
-pub fn mouse_move_relative<T, F, I, Q>(point: (T, F), offset: (I, Q)) -> (Float, Float)
+pub fn mouse_move_relative<T, F, I, Q>(point: (T, F), offset: (I, Q)) -> RhaiResult<(Float, Float)>
 where
     T: Into<Float>,
     F: Into<Float>,
@@ -254,210 +635,317 @@ where
     );
     let x = point.0 + offset.0;
     let y = point.1 + offset.1;
-    mouse_move(x, y);
-    (x, y)
+    mouse_move(x, y)?;
+    Ok((x, y))
 }
-pub fn wheel_up<T>(delta_y: T)
+pub fn wheel_up<T>(delta_y: T) -> RhaiResult
 where
     T: Into<Float>,
 {
     let delta_y = delta_y.into() as Int;
     let delta_y = 0 - delta_y.abs();
-    wheel(delta_y);
+    wheel("wheel_up", 0, delta_y)
 }
-pub fn wheel_down<T>(delta_y: T)
+pub fn wheel_down<T>(delta_y: T) -> RhaiResult
 where
     T: Into<Float>,
 {
     let delta_y = delta_y.into() as Int;
-    wheel(delta_y.abs());
+    wheel("wheel_down", 0, delta_y.abs())
+}
+pub fn wheel_left<T>(delta_x: T) -> RhaiResult
+where
+    T: Into<Float>,
+{
+    let delta_x = delta_x.into() as Int;
+    wheel("wheel_left", 0 - delta_x.abs(), 0)
+}
+pub fn wheel_right<T>(delta_x: T) -> RhaiResult
+where
+    T: Into<Float>,
+{
+    let delta_x = delta_x.into() as Int;
+    wheel("wheel_right", delta_x.abs(), 0)
+}
+///scroll both axes at once, positive is right and down
+pub fn wheel_by<T, F>(delta_x: T, delta_y: F) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+{
+    wheel("wheel_by", delta_x.into() as Int, delta_y.into() as Int)
+}
+pub fn key_press(key: Key) -> RhaiResult {
+    simulate(
+        "key_press",
+        Event::KeyPress {
+            key,
+            elapse: 0,
+            duration: 0,
+        },
+    )
+}
+pub fn key_release(key: Key) -> RhaiResult {
+    simulate(
+        "key_release",
+        Event::KeyRelease {
+            key,
+            elapse: 0,
+            duration: 0,
+        },
+    )
+}
+///press and release key, a failure is reported as function
+fn tap(function: &str, key: Key, hold: Float) -> RhaiResult {
+    simulate(
+        function,
+        Event::KeyPress {
+            key,
+            elapse: 0,
+            duration: 0,
+        },
+    )?;
+    delay(hold)?;
+    simulate(
+        function,
+        Event::KeyRelease {
+            key,
+            elapse: 0,
+            duration: 0,
+        },
+    )
+}
+pub fn key_click(key: Key) -> RhaiResult {
+    tap("key_click", key, 10 as Float)
 }
-pub fn key_press(key: Key) {
-    let _ = Event::KeyPress {
-        key,
-        elapse: 0,
-        duration: 0,
+pub fn key_click_hold<T>(key: Key, hold: T) -> RhaiResult
+where
+    T: Into<Float>,
+{
+    tap("key_click_hold", key, hold.into())
+}
+///type text one key at a time, a press every interval millis held for hold millis,
+///only characters of TYPED_KEYS, shift and the like stay with the caller
+pub fn type_keys<T, F>(text: &str, hold: T, interval: F) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+{
+    let (hold, interval) = (hold.into(), interval.into());
+    let keys = text
+        .chars()
+        .map(|character| {
+            typed_key(character).ok_or_else(|| {
+                invalid_argument("type_keys", format!("cannot type {:?}", character))
+            })
+        })
+        .collect::<RhaiResult<Vec<Key>>>()?;
+    for (index, key) in keys.into_iter().enumerate() {
+        if index > 0 {
+            delay((interval - hold).max(0 as Float))?;
+        }
+        tap("type_keys", key, hold)?;
     }
-    .simulate();
+    Ok(())
 }
-pub fn key_release(key: Key) {
-    let _ = Event::KeyRelease {
-        key,
-        elapse: 0,
-        duration: 0,
+fn wait(function: &str, millis: u64) -> RhaiResult {
+    if !control::sleep(Duration::from_millis(scale_millis(millis))) {
+        return Err(fail(function, SimulateError::Stopped));
     }
-    .simulate();
-}
-pub fn key_click(key: Key) {
-    key_press(key);
-    delay(10);
-    key_release(key);
-}
-pub fn delay<T>(duration: T)
-where
-    T: Into<Float>,
-{
-    let duration = duration.into() as u64;
-    std::thread::sleep(Duration::from_millis(duration));
-}
-
-#[cfg(target_os = "macos")]
-pub fn select_all() {
-    key_press(Key::MetaLeft);
-    delay(10);
-    key_press(Key::KeyA);
-    delay(5);
-    key_release(Key::KeyA);
-    delay(5);
-    key_release(Key::MetaLeft);
-}
-
-#[cfg(target_os = "windows")]
-pub fn select_all() {
-    key_press(Key::ControlLeft);
-    delay(10);
-    key_press(Key::KeyA);
-    delay(5);
-    key_release(Key::KeyA);
-    delay(5);
-    key_release(Key::ControlLeft);
-}
-
-#[cfg(target_os = "macos")]
-pub fn paste_text(text: &str) {
-    let mut clipboard = Clipboard::new().unwrap();
-    clipboard.set_text(text).unwrap();
-    key_press(Key::MetaLeft);
-    delay(10);
-    key_press(Key::KeyV);
-    delay(5);
-    key_release(Key::KeyV);
-    delay(5);
-    key_release(Key::MetaLeft);
-}
-
-#[cfg(target_os = "windows")]
-pub fn paste_text(text: &str) {
-    let mut clipboard = Clipboard::new().unwrap();
-    clipboard.set_text(text).unwrap();
-    key_press(Key::ControlLeft);
-    delay(10);
-    key_press(Key::KeyV);
-    delay(5);
-    key_release(Key::KeyV);
-    delay(5);
-    key_release(Key::ControlLeft);
-}
-
-pub fn run(content: String) -> Result<(), SimulateError> {
-    let mut engine = Engine::new();
+    Ok(())
+}
+///recorded delays drift by the run jitter
+pub fn delay<T>(duration: T) -> RhaiResult
+where
+    T: Into<Float>,
+{
+    wait("delay", jitter_millis(duration.into() as u64))
+}
+pub fn delay_random<T, F>(min: T, max: F) -> RhaiResult
+where
+    T: Into<Float>,
+    F: Into<Float>,
+{
+    let millis = random_float(min.into(), max.into()).max(0 as Float);
+    wait("delay_random", millis.round() as u64)
+}
+pub fn rand_int(min: Int, max: Int) -> Int {
+    random_int(min, max)
+}
+pub fn rand_float<T, F>(min: T, max: F) -> Float
+where
+    T: Into<Float>,
+    F: Into<Float>,
+{
+    random_float(min.into(), max.into())
+}
+pub fn choose(array: Array) -> RhaiResult<Dynamic> {
+    if array.is_empty() {
+        return Err(invalid_argument("choose", "empty array".to_string()));
+    }
+    let index = random_int(0, array.len() as Int - 1) as usize;
+    Ok(array[index].clone())
+}
+
+///press key while holding the platform primary modifier, a failure is reported as function
+fn primary_tap(function: &str, key: Key) -> RhaiResult {
+    let modifier = Key::primary_modifier();
+    simulate(
+        function,
+        Event::KeyPress {
+            key: modifier,
+            elapse: 0,
+            duration: 0,
+        },
+    )?;
+    delay(10)?;
+    tap(function, key, 5 as Float)?;
+    delay(5)?;
+    simulate(
+        function,
+        Event::KeyRelease {
+            key: modifier,
+            elapse: 0,
+            duration: 0,
+        },
+    )
+}
+
+///press key while holding the platform primary modifier, e.g. Ctrl+A or Cmd+A
+pub fn shortcut(key: Key) -> RhaiResult {
+    primary_tap("shortcut", key)
+}
+
+pub fn select_all() -> RhaiResult {
+    primary_tap("select_all", Key::KeyA)
+}
+
+pub fn copy() -> RhaiResult {
+    primary_tap("copy", Key::KeyC)
+}
+
+pub fn cut() -> RhaiResult {
+    primary_tap("cut", Key::KeyX)
+}
+
+pub fn paste() -> RhaiResult {
+    primary_tap("paste", Key::KeyV)
+}
+
+pub fn undo() -> RhaiResult {
+    primary_tap("undo", Key::KeyZ)
+}
+
+pub fn save() -> RhaiResult {
+    primary_tap("save", Key::KeyS)
+}
+
+pub fn paste_text(text: &str) -> RhaiResult {
+    let result = Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text));
+    if let Err(err) = result {
+        return Err(fail(
+            "paste_text",
+            SimulateError::Clipboard(err.to_string()),
+        ));
+    }
+    primary_tap("paste_text", Key::KeyV)
+}
+
+///names of the key variables a script can use
+pub fn key_variables() -> Vec<(&'static str, Key)> {
+    let mut variables = KEY_NAMES.to_vec();
+    variables.extend(KEY_ALIASES);
+    variables.push(("Primary", Key::primary_modifier()));
+    variables
+}
+
+///a key without a name, by its platform code
+fn key_code(code: Int) -> RhaiResult<Key> {
+    match UInt::try_from(code) {
+        Ok(code) => Ok(Key::Unknown(code)),
+        Err(_) => Err(invalid_argument(
+            "key_code",
+            format!("code {} is negative", code),
+        )),
+    }
+}
+
+///a button without a name, by its platform code
+fn button_code(code: Int) -> RhaiResult<Button> {
+    match u8::try_from(code) {
+        Ok(code) => Ok(Button::Unknown(code)),
+        Err(_) => Err(invalid_argument(
+            "button_code",
+            format!("code {} is out of 0..=255", code),
+        )),
+    }
+}
+
+///every button as Button::name
+fn button_module() -> Module {
+    let mut module = Module::new();
+    for (name, button) in BUTTON_NAMES {
+        module.set_var(name, button);
+    }
+    module
+}
+
+///key by its script name, e.g. key("a")
+fn key(name: &str) -> RhaiResult<Key> {
+    match name {
+        "Primary" => Ok(Key::primary_modifier()),
+        name => name
+            .parse()
+            .map_err(|_| invalid_argument("key", format!("unknown key {}", name))),
+    }
+}
+
+///every key as Key::name, so user variables never shadow a key
+fn key_module() -> Module {
+    let mut module = Module::new();
+    for (name, key) in key_variables() {
+        module.set_var(name, key);
+    }
+    module
+}
+
+///scripts recorded before Key::name use bare key names, see set_legacy_keys
+static LEGACY_KEYS: AtomicBool = AtomicBool::new(true);
+
+///whether scripts also get every key as a global variable
+pub fn set_legacy_keys(enabled: bool) {
+    LEGACY_KEYS.store(enabled, Ordering::SeqCst);
+}
+
+///legacy key names if enabled, constant so no loop can change them for the next one
+fn base_scope() -> Scope<'static> {
     let mut scope = Scope::new();
+    if LEGACY_KEYS.load(Ordering::SeqCst) {
+        for (name, key) in key_variables() {
+            scope.push_constant(name, key);
+        }
+    }
+    scope
+}
+
+///run f with the base scope plus the position of the current loop,
+///whatever the loop declared is rewound afterwards instead of cloning the scope every loop
+fn with_scope<T>(iteration: &Iteration, f: impl FnOnce(&mut Scope<'static>) -> T) -> T {
+    SCOPE.with(|scope| {
+        let mut scope = scope.borrow_mut();
+        let base = scope.len();
+        scope.push_constant("loop_index", iteration.index);
+        scope.push_constant("loop_count", iteration.count);
+        let result = f(&mut scope);
+        scope.rewind(base);
+        result
+    })
+}
 
-    scope.push("Alt", Key::Alt);
-    scope.push("AltGr", Key::AltGr);
-    scope.push("Backspace", Key::Backspace);
-    scope.push("CapsLock", Key::CapsLock);
-    scope.push("ControlLeft", Key::ControlLeft);
-    scope.push("ControlRight", Key::ControlRight);
-    scope.push("Delete", Key::Delete);
-    scope.push("DownArrow", Key::DownArrow);
-    scope.push("End", Key::End);
-    scope.push("Escape", Key::Escape);
-    scope.push("f1", Key::F1);
-    scope.push("f10", Key::F10);
-    scope.push("f11", Key::F11);
-    scope.push("f12", Key::F12);
-    scope.push("f2", Key::F2);
-    scope.push("f3", Key::F3);
-    scope.push("f4", Key::F4);
-    scope.push("f5", Key::F5);
-    scope.push("f6", Key::F6);
-    scope.push("f7", Key::F7);
-    scope.push("f8", Key::F8);
-    scope.push("f9", Key::F9);
-    scope.push("Home", Key::Home);
-    scope.push("LeftArrow", Key::LeftArrow);
-    scope.push("MetaLeft", Key::MetaLeft);
-    scope.push("MetaRight", Key::MetaRight);
-    scope.push("PageDown", Key::PageDown);
-    scope.push("PageUp", Key::PageUp);
-    scope.push("Return", Key::Return);
-    scope.push("RightArrow", Key::RightArrow);
-    scope.push("ShiftLeft", Key::ShiftLeft);
-    scope.push("ShiftRight", Key::ShiftRight);
-    scope.push("Space", Key::Space);
-    scope.push("Tab", Key::Tab);
-    scope.push("UpArrow", Key::UpArrow);
-    scope.push("PrintScreen", Key::PrintScreen);
-    scope.push("ScrollLock", Key::ScrollLock);
-    scope.push("Pause", Key::Pause);
-    scope.push("NumLock", Key::NumLock);
-    scope.push("BackQuote", Key::BackQuote);
-    scope.push("n1", Key::Num1);
-    scope.push("n2", Key::Num2);
-    scope.push("n3", Key::Num3);
-    scope.push("n4", Key::Num4);
-    scope.push("n5", Key::Num5);
-    scope.push("n6", Key::Num6);
-    scope.push("n7", Key::Num7);
-    scope.push("n8", Key::Num8);
-    scope.push("n9", Key::Num9);
-    scope.push("n0", Key::Num0);
-    scope.push("Minus", Key::Minus);
-    scope.push("Equal", Key::Equal);
-    scope.push("q", Key::KeyQ);
-    scope.push("w", Key::KeyW);
-    scope.push("e", Key::KeyE);
-    scope.push("r", Key::KeyR);
-    scope.push("t", Key::KeyT);
-    scope.push("y", Key::KeyY);
-    scope.push("u", Key::KeyU);
-    scope.push("i", Key::KeyI);
-    scope.push("o", Key::KeyO);
-    scope.push("p", Key::KeyP);
-    scope.push("LeftBracket", Key::LeftBracket);
-    scope.push("RightBracket", Key::RightBracket);
-    scope.push("a", Key::KeyA);
-    scope.push("s", Key::KeyS);
-    scope.push("d", Key::KeyD);
-    scope.push("f", Key::KeyF);
-    scope.push("g", Key::KeyG);
-    scope.push("h", Key::KeyH);
-    scope.push("j", Key::KeyJ);
-    scope.push("k", Key::KeyK);
-    scope.push("l", Key::KeyL);
-    scope.push("SemiColon", Key::SemiColon);
-    scope.push("Quote", Key::Quote);
-    scope.push("BackSlash", Key::BackSlash);
-    scope.push("IntlBackslash", Key::IntlBackslash);
-    scope.push("z", Key::KeyZ);
-    scope.push("x", Key::KeyX);
-    scope.push("c", Key::KeyC);
-    scope.push("v", Key::KeyV);
-    scope.push("b", Key::KeyB);
-    scope.push("n", Key::KeyN);
-    scope.push("m", Key::KeyM);
-    scope.push("Comma", Key::Comma);
-    scope.push("Dot", Key::Dot);
-    scope.push("Slash", Key::Slash);
-    scope.push("Insert", Key::Insert);
-    scope.push("KpReturn", Key::KpReturn);
-    scope.push("KpMinus", Key::KpMinus);
-    scope.push("KpPlus", Key::KpPlus);
-    scope.push("KpMultiply", Key::KpMultiply);
-    scope.push("KpDivide", Key::KpDivide);
-    scope.push("Kp0", Key::Kp0);
-    scope.push("Kp1", Key::Kp1);
-    scope.push("Kp2", Key::Kp2);
-    scope.push("Kp3", Key::Kp3);
-    scope.push("Kp4", Key::Kp4);
-    scope.push("Kp5", Key::Kp5);
-    scope.push("Kp6", Key::Kp6);
-    scope.push("Kp7", Key::Kp7);
-    scope.push("Kp8", Key::Kp8);
-    scope.push("Kp9", Key::Kp9);
-    scope.push("KpDelete", Key::KpDelete);
-    scope.push("Function", Key::Function);
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.register_static_module("Key", key_module().into());
+    engine.register_static_module("Button", button_module().into());
     engine
         .register_fn("drag_left_instant", drag_left_instant::<Int, Int>)
         .register_fn("drag_left_instant", drag_left_instant::<Float, Float>)
@@ -743,6 +1231,27 @@ pub fn run(content: String) -> Result<(), SimulateError> {
         .register_fn("mouse_move", mouse_move::<Float, Float>)
         .register_fn("mouse_move", mouse_move::<Int, Float>)
         .register_fn("mouse_move", mouse_move::<Float, Int>);
+    engine
+        .register_fn("mouse_move", mouse_move_smooth::<Int, Int, Int>)
+        .register_fn("mouse_move", mouse_move_smooth::<Int, Int, Float>)
+        .register_fn("mouse_move", mouse_move_smooth::<Int, Float, Int>)
+        .register_fn("mouse_move", mouse_move_smooth::<Int, Float, Float>)
+        .register_fn("mouse_move", mouse_move_smooth::<Float, Int, Int>)
+        .register_fn("mouse_move", mouse_move_smooth::<Float, Int, Float>)
+        .register_fn("mouse_move", mouse_move_smooth::<Float, Float, Int>)
+        .register_fn("mouse_move", mouse_move_smooth::<Float, Float, Float>);
+    engine
+        .register_fn("set_path_curve", set_path_curve)
+        .register_fn("set_path_curve", set_path_bend)
+        .register_fn("set_path_curve", |name: &str, bend: Int| {
+            set_path_bend(name, bend as Float)
+        });
+    engine.register_fn("set_path_easing", set_path_easing);
+    engine
+        .register_fn("set_path_jitter", set_path_jitter::<Int>)
+        .register_fn("set_path_jitter", set_path_jitter::<Float>);
+    engine.register_fn("set_path_points", set_path_points);
+    engine.register_fn("set_path_step", set_path_step);
     engine
         .register_fn(
             "mouse_move_relative",
@@ -817,11 +1326,186 @@ pub fn run(content: String) -> Result<(), SimulateError> {
     engine
         .register_fn("delay", delay::<Int>)
         .register_fn("delay", delay::<Float>);
+    engine
+        .register_fn("delay_random", delay_random::<Int, Int>)
+        .register_fn("delay_random", delay_random::<Int, Float>)
+        .register_fn("delay_random", delay_random::<Float, Int>)
+        .register_fn("delay_random", delay_random::<Float, Float>);
+    engine.register_fn("rand_int", rand_int);
+    engine
+        .register_fn("rand_float", rand_float::<Int, Int>)
+        .register_fn("rand_float", rand_float::<Int, Float>)
+        .register_fn("rand_float", rand_float::<Float, Int>)
+        .register_fn("rand_float", rand_float::<Float, Float>);
+    engine.register_fn("choose", choose);
+    engine.register_fn("button_code", button_code);
+    engine.register_fn("key", key);
+    engine.register_fn("key_code", key_code);
     engine.register_fn("key_press", key_press);
     engine.register_fn("key_release", key_release);
     engine.register_fn("key_click", key_click);
+    engine
+        .register_fn("key_click", key_click_hold::<Int>)
+        .register_fn("key_click", key_click_hold::<Float>);
+    engine
+        .register_fn("click_left", click_left_hold::<Int, Int, Int>)
+        .register_fn("click_left", click_left_hold::<Int, Int, Float>)
+        .register_fn("click_left", click_left_hold::<Int, Float, Int>)
+        .register_fn("click_left", click_left_hold::<Int, Float, Float>)
+        .register_fn("click_left", click_left_hold::<Float, Int, Int>)
+        .register_fn("click_left", click_left_hold::<Float, Int, Float>)
+        .register_fn("click_left", click_left_hold::<Float, Float, Int>)
+        .register_fn("click_left", click_left_hold::<Float, Float, Float>);
+    engine
+        .register_fn("click_right", click_right_hold::<Int, Int, Int>)
+        .register_fn("click_right", click_right_hold::<Int, Int, Float>)
+        .register_fn("click_right", click_right_hold::<Int, Float, Int>)
+        .register_fn("click_right", click_right_hold::<Int, Float, Float>)
+        .register_fn("click_right", click_right_hold::<Float, Int, Int>)
+        .register_fn("click_right", click_right_hold::<Float, Int, Float>)
+        .register_fn("click_right", click_right_hold::<Float, Float, Int>)
+        .register_fn("click_right", click_right_hold::<Float, Float, Float>);
+    engine
+        .register_fn("click", click_button_hold::<Int, Int, Int>)
+        .register_fn("click", click_button_hold::<Int, Int, Float>)
+        .register_fn("click", click_button_hold::<Int, Float, Int>)
+        .register_fn("click", click_button_hold::<Int, Float, Float>)
+        .register_fn("click", click_button_hold::<Float, Int, Int>)
+        .register_fn("click", click_button_hold::<Float, Int, Float>)
+        .register_fn("click", click_button_hold::<Float, Float, Int>)
+        .register_fn("click", click_button_hold::<Float, Float, Float>);
+    engine
+        .register_fn("double_click", double_click::<Int, Int, Int, Int>)
+        .register_fn("double_click", double_click::<Int, Int, Int, Float>)
+        .register_fn("double_click", double_click::<Int, Int, Float, Int>)
+        .register_fn("double_click", double_click::<Int, Int, Float, Float>)
+        .register_fn("double_click", double_click::<Int, Float, Int, Int>)
+        .register_fn("double_click", double_click::<Int, Float, Int, Float>)
+        .register_fn("double_click", double_click::<Int, Float, Float, Int>)
+        .register_fn("double_click", double_click::<Int, Float, Float, Float>)
+        .register_fn("double_click", double_click::<Float, Int, Int, Int>)
+        .register_fn("double_click", double_click::<Float, Int, Int, Float>)
+        .register_fn("double_click", double_click::<Float, Int, Float, Int>)
+        .register_fn("double_click", double_click::<Float, Int, Float, Float>)
+        .register_fn("double_click", double_click::<Float, Float, Int, Int>)
+        .register_fn("double_click", double_click::<Float, Float, Int, Float>)
+        .register_fn("double_click", double_click::<Float, Float, Float, Int>)
+        .register_fn("double_click", double_click::<Float, Float, Float, Float>);
+    engine
+        .register_fn("drag_left", drag_left::<Int, Int, Int, Int, Int>)
+        .register_fn("drag_left", drag_left::<Int, Int, Int, Int, Float>)
+        .register_fn("drag_left", drag_left::<Int, Int, Int, Float, Int>)
+        .register_fn("drag_left", drag_left::<Int, Int, Int, Float, Float>)
+        .register_fn("drag_left", drag_left::<Int, Int, Float, Int, Int>)
+        .register_fn("drag_left", drag_left::<Int, Int, Float, Int, Float>)
+        .register_fn("drag_left", drag_left::<Int, Int, Float, Float, Int>)
+        .register_fn("drag_left", drag_left::<Int, Int, Float, Float, Float>)
+        .register_fn("drag_left", drag_left::<Int, Float, Int, Int, Int>)
+        .register_fn("drag_left", drag_left::<Int, Float, Int, Int, Float>)
+        .register_fn("drag_left", drag_left::<Int, Float, Int, Float, Int>)
+        .register_fn("drag_left", drag_left::<Int, Float, Int, Float, Float>)
+        .register_fn("drag_left", drag_left::<Int, Float, Float, Int, Int>)
+        .register_fn("drag_left", drag_left::<Int, Float, Float, Int, Float>)
+        .register_fn("drag_left", drag_left::<Int, Float, Float, Float, Int>)
+        .register_fn("drag_left", drag_left::<Int, Float, Float, Float, Float>)
+        .register_fn("drag_left", drag_left::<Float, Int, Int, Int, Int>)
+        .register_fn("drag_left", drag_left::<Float, Int, Int, Int, Float>)
+        .register_fn("drag_left", drag_left::<Float, Int, Int, Float, Int>)
+        .register_fn("drag_left", drag_left::<Float, Int, Int, Float, Float>)
+        .register_fn("drag_left", drag_left::<Float, Int, Float, Int, Int>)
+        .register_fn("drag_left", drag_left::<Float, Int, Float, Int, Float>)
+        .register_fn("drag_left", drag_left::<Float, Int, Float, Float, Int>)
+        .register_fn("drag_left", drag_left::<Float, Int, Float, Float, Float>)
+        .register_fn("drag_left", drag_left::<Float, Float, Int, Int, Int>)
+        .register_fn("drag_left", drag_left::<Float, Float, Int, Int, Float>)
+        .register_fn("drag_left", drag_left::<Float, Float, Int, Float, Int>)
+        .register_fn("drag_left", drag_left::<Float, Float, Int, Float, Float>)
+        .register_fn("drag_left", drag_left::<Float, Float, Float, Int, Int>)
+        .register_fn("drag_left", drag_left::<Float, Float, Float, Int, Float>)
+        .register_fn("drag_left", drag_left::<Float, Float, Float, Float, Int>)
+        .register_fn("drag_left", drag_left::<Float, Float, Float, Float, Float>);
+    engine
+        .register_fn("drag_right", drag_right::<Int, Int, Int, Int, Int>)
+        .register_fn("drag_right", drag_right::<Int, Int, Int, Int, Float>)
+        .register_fn("drag_right", drag_right::<Int, Int, Int, Float, Int>)
+        .register_fn("drag_right", drag_right::<Int, Int, Int, Float, Float>)
+        .register_fn("drag_right", drag_right::<Int, Int, Float, Int, Int>)
+        .register_fn("drag_right", drag_right::<Int, Int, Float, Int, Float>)
+        .register_fn("drag_right", drag_right::<Int, Int, Float, Float, Int>)
+        .register_fn("drag_right", drag_right::<Int, Int, Float, Float, Float>)
+        .register_fn("drag_right", drag_right::<Int, Float, Int, Int, Int>)
+        .register_fn("drag_right", drag_right::<Int, Float, Int, Int, Float>)
+        .register_fn("drag_right", drag_right::<Int, Float, Int, Float, Int>)
+        .register_fn("drag_right", drag_right::<Int, Float, Int, Float, Float>)
+        .register_fn("drag_right", drag_right::<Int, Float, Float, Int, Int>)
+        .register_fn("drag_right", drag_right::<Int, Float, Float, Int, Float>)
+        .register_fn("drag_right", drag_right::<Int, Float, Float, Float, Int>)
+        .register_fn("drag_right", drag_right::<Int, Float, Float, Float, Float>)
+        .register_fn("drag_right", drag_right::<Float, Int, Int, Int, Int>)
+        .register_fn("drag_right", drag_right::<Float, Int, Int, Int, Float>)
+        .register_fn("drag_right", drag_right::<Float, Int, Int, Float, Int>)
+        .register_fn("drag_right", drag_right::<Float, Int, Int, Float, Float>)
+        .register_fn("drag_right", drag_right::<Float, Int, Float, Int, Int>)
+        .register_fn("drag_right", drag_right::<Float, Int, Float, Int, Float>)
+        .register_fn("drag_right", drag_right::<Float, Int, Float, Float, Int>)
+        .register_fn("drag_right", drag_right::<Float, Int, Float, Float, Float>)
+        .register_fn("drag_right", drag_right::<Float, Float, Int, Int, Int>)
+        .register_fn("drag_right", drag_right::<Float, Float, Int, Int, Float>)
+        .register_fn("drag_right", drag_right::<Float, Float, Int, Float, Int>)
+        .register_fn("drag_right", drag_right::<Float, Float, Int, Float, Float>)
+        .register_fn("drag_right", drag_right::<Float, Float, Float, Int, Int>)
+        .register_fn("drag_right", drag_right::<Float, Float, Float, Int, Float>)
+        .register_fn("drag_right", drag_right::<Float, Float, Float, Float, Int>)
+        .register_fn(
+            "drag_right",
+            drag_right::<Float, Float, Float, Float, Float>,
+        );
+    engine
+        .register_fn("drag", drag_button::<Int, Int, Int, Int, Int>)
+        .register_fn("drag", drag_button::<Int, Int, Int, Int, Float>)
+        .register_fn("drag", drag_button::<Int, Int, Int, Float, Int>)
+        .register_fn("drag", drag_button::<Int, Int, Int, Float, Float>)
+        .register_fn("drag", drag_button::<Int, Int, Float, Int, Int>)
+        .register_fn("drag", drag_button::<Int, Int, Float, Int, Float>)
+        .register_fn("drag", drag_button::<Int, Int, Float, Float, Int>)
+        .register_fn("drag", drag_button::<Int, Int, Float, Float, Float>)
+        .register_fn("drag", drag_button::<Int, Float, Int, Int, Int>)
+        .register_fn("drag", drag_button::<Int, Float, Int, Int, Float>)
+        .register_fn("drag", drag_button::<Int, Float, Int, Float, Int>)
+        .register_fn("drag", drag_button::<Int, Float, Int, Float, Float>)
+        .register_fn("drag", drag_button::<Int, Float, Float, Int, Int>)
+        .register_fn("drag", drag_button::<Int, Float, Float, Int, Float>)
+        .register_fn("drag", drag_button::<Int, Float, Float, Float, Int>)
+        .register_fn("drag", drag_button::<Int, Float, Float, Float, Float>)
+        .register_fn("drag", drag_button::<Float, Int, Int, Int, Int>)
+        .register_fn("drag", drag_button::<Float, Int, Int, Int, Float>)
+        .register_fn("drag", drag_button::<Float, Int, Int, Float, Int>)
+        .register_fn("drag", drag_button::<Float, Int, Int, Float, Float>)
+        .register_fn("drag", drag_button::<Float, Int, Float, Int, Int>)
+        .register_fn("drag", drag_button::<Float, Int, Float, Int, Float>)
+        .register_fn("drag", drag_button::<Float, Int, Float, Float, Int>)
+        .register_fn("drag", drag_button::<Float, Int, Float, Float, Float>)
+        .register_fn("drag", drag_button::<Float, Float, Int, Int, Int>)
+        .register_fn("drag", drag_button::<Float, Float, Int, Int, Float>)
+        .register_fn("drag", drag_button::<Float, Float, Int, Float, Int>)
+        .register_fn("drag", drag_button::<Float, Float, Int, Float, Float>)
+        .register_fn("drag", drag_button::<Float, Float, Float, Int, Int>)
+        .register_fn("drag", drag_button::<Float, Float, Float, Int, Float>)
+        .register_fn("drag", drag_button::<Float, Float, Float, Float, Int>)
+        .register_fn("drag", drag_button::<Float, Float, Float, Float, Float>);
+    engine
+        .register_fn("type_keys", type_keys::<Int, Int>)
+        .register_fn("type_keys", type_keys::<Int, Float>)
+        .register_fn("type_keys", type_keys::<Float, Int>)
+        .register_fn("type_keys", type_keys::<Float, Float>);
+    engine.register_fn("shortcut", shortcut);
     engine.register_fn("paste_text", paste_text);
     engine.register_fn("select_all", select_all);
+    engine.register_fn("copy", copy);
+    engine.register_fn("cut", cut);
+    engine.register_fn("paste", paste);
+    engine.register_fn("undo", undo);
+    engine.register_fn("save", save);
     engine
         .register_fn("button_left_press", button_left_press::<Int, Int>)
         .register_fn("button_left_press", button_left_press::<Int, Float>)
@@ -842,19 +1526,336 @@ pub fn run(content: String) -> Result<(), SimulateError> {
         .register_fn("button_right_release", button_right_release::<Int, Float>)
         .register_fn("button_right_release", button_right_release::<Float, Int>)
         .register_fn("button_right_release", button_right_release::<Float, Float>);
+    engine
+        .register_fn("button_press", button_press::<Int, Int>)
+        .register_fn("button_press", button_press::<Int, Float>)
+        .register_fn("button_press", button_press::<Float, Int>)
+        .register_fn("button_press", button_press::<Float, Float>);
+    engine
+        .register_fn("button_release", button_release::<Int, Int>)
+        .register_fn("button_release", button_release::<Int, Float>)
+        .register_fn("button_release", button_release::<Float, Int>)
+        .register_fn("button_release", button_release::<Float, Float>);
+    engine
+        .register_fn("click", click_button::<Int, Int>)
+        .register_fn("click", click_button::<Int, Float>)
+        .register_fn("click", click_button::<Float, Int>)
+        .register_fn("click", click_button::<Float, Float>);
+    engine.register_fn("button_click", button_click);
+    engine
+        .register_fn("drag_instant", drag_instant::<Int, Int>)
+        .register_fn("drag_instant", drag_instant::<Int, Float>)
+        .register_fn("drag_instant", drag_instant::<Float, Int>)
+        .register_fn("drag_instant", drag_instant::<Float, Float>);
+    engine
+        .register_fn("drag_relative", drag_relative::<Int, Int, Int, Int, Int>)
+        .register_fn("drag_relative", drag_relative::<Int, Int, Int, Int, Float>)
+        .register_fn("drag_relative", drag_relative::<Int, Int, Int, Float, Int>)
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Int, Int, Int, Float, Float>,
+        )
+        .register_fn("drag_relative", drag_relative::<Int, Int, Float, Int, Int>)
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Int, Int, Float, Int, Float>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Int, Int, Float, Float, Int>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Int, Int, Float, Float, Float>,
+        )
+        .register_fn("drag_relative", drag_relative::<Int, Float, Int, Int, Int>)
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Int, Float, Int, Int, Float>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Int, Float, Int, Float, Int>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Int, Float, Int, Float, Float>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Int, Float, Float, Int, Int>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Int, Float, Float, Int, Float>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Int, Float, Float, Float, Int>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Int, Float, Float, Float, Float>,
+        )
+        .register_fn("drag_relative", drag_relative::<Float, Int, Int, Int, Int>)
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Int, Int, Int, Float>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Int, Int, Float, Int>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Int, Int, Float, Float>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Int, Float, Int, Int>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Int, Float, Int, Float>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Int, Float, Float, Int>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Int, Float, Float, Float>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Float, Int, Int, Int>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Float, Int, Int, Float>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Float, Int, Float, Int>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Float, Int, Float, Float>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Float, Float, Int, Int>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Float, Float, Int, Float>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Float, Float, Float, Int>,
+        )
+        .register_fn(
+            "drag_relative",
+            drag_relative::<Float, Float, Float, Float, Float>,
+        );
+    engine
+        .register_fn("wheel_left", wheel_left::<Int>)
+        .register_fn("wheel_left", wheel_left::<Float>);
+    engine
+        .register_fn("wheel_right", wheel_right::<Int>)
+        .register_fn("wheel_right", wheel_right::<Float>);
+    engine
+        .register_fn("wheel", wheel_by::<Int, Int>)
+        .register_fn("wheel", wheel_by::<Int, Float>)
+        .register_fn("wheel", wheel_by::<Float, Int>)
+        .register_fn("wheel", wheel_by::<Float, Float>);
 
-    engine.on_progress(move |_opt| loop {
-        match SIMULATE_STATE_CHANNEL.1.try_recv() {
-            Ok(result) if result => {
-                info!("get true form SIMULATE_STATE_CHANNEL, simulating should be paused");
-                return Some("stop".into());
-            }
-            Ok(_) => return None,
-            Err(_) => return None,
+    //blocks here while paused
+    engine.on_progress(move |_opt| {
+        if control::poll() {
+            return Some("stop".into());
         }
+        None
     });
-    if let Err(err) = engine.run_with_scope(&mut scope, content.as_str()) {
-        return Err(SimulateError::Rhai(err));
+    engine
+}
+
+//every run spawns its own thread, so these live for one playback session
+//and all loops of that session share them
+thread_local! {
+    static ENGINE: Engine = engine();
+    static SCOPE: RefCell<Scope<'static>> = RefCell::new(base_scope());
+    static SCRIPT: RefCell<Option<(String, AST)>> = const { RefCell::new(None) };
+    static PREDICATE: RefCell<Option<(String, AST)>> = const { RefCell::new(None) };
+}
+
+///compile source unless the cache already holds the same text
+fn compile(
+    engine: &Engine,
+    cache: &RefCell<Option<(String, AST)>>,
+    source: &str,
+) -> Result<(), SimulateError> {
+    if matches!(&*cache.borrow(), Some((cached, _)) if cached == source) {
+        return Ok(());
     }
+    let start = Instant::now();
+    let ast = engine
+        .compile(source)
+        .map_err(|err| SimulateError::Rhai(err.into()))?;
+    info!("compiled {} bytes in {:?}", source.len(), start.elapsed());
+    *cache.borrow_mut() = Some((source.to_string(), ast));
     Ok(())
 }
+
+pub fn run(content: &str, iteration: &Iteration) -> Result<(), SimulateError> {
+    set_path_style(PathStyle::default());
+    ENGINE.with(|engine| {
+        SCRIPT.with(|cache| {
+            compile(engine, cache, content)?;
+            let cache = cache.borrow();
+            let (_, ast) = cache.as_ref().unwrap();
+            let start = Instant::now();
+            let result = with_scope(iteration, |scope| engine.run_ast_with_scope(scope, ast));
+            info!("loop {} ran in {:?}", iteration.index, start.elapsed());
+            result.map_err(SimulateError::Rhai)
+        })
+    })
+}
+
+///build the engine and compile content on every call as each loop once did, kept to measure the cache
+pub fn run_uncached(content: &str, iteration: &Iteration) -> Result<(), SimulateError> {
+    set_path_style(PathStyle::default());
+    let engine = engine();
+    let ast = engine
+        .compile(content)
+        .map_err(|err| SimulateError::Rhai(err.into()))?;
+    let mut scope = base_scope();
+    scope.push_constant("loop_index", iteration.index);
+    scope.push_constant("loop_count", iteration.count);
+    engine
+        .run_ast_with_scope(&mut scope, &ast)
+        .map_err(SimulateError::Rhai)
+}
+
+///evaluate a loop condition, it sees the same functions and variables as the macro
+pub fn eval_predicate(predicate: &str, iteration: &Iteration) -> Result<bool, SimulateError> {
+    ENGINE.with(|engine| {
+        PREDICATE.with(|cache| {
+            compile(engine, cache, predicate)?;
+            let cache = cache.borrow();
+            let (_, ast) = cache.as_ref().unwrap();
+            with_scope(iteration, |scope| {
+                engine.eval_ast_with_scope::<bool>(scope, ast)
+            })
+            .map_err(SimulateError::Rhai)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval<T: Clone + 'static>(script: &str) -> T {
+        ENGINE.with(|engine| {
+            engine
+                .eval_with_scope::<T>(&mut base_scope(), script)
+                .unwrap_or_else(|err| panic!("{}: {}", script, err))
+        })
+    }
+
+    #[test]
+    fn a_seed_replays_script_randomness() {
+        let (_guard, _) = super::super::backend::install_mock();
+        let script = "let values = [];
+            for i in 0..10 {
+                values.push(rand_int(1, 6));
+                values.push(rand_float(0, 1.0));
+                values.push(choose([\"a\", 2, 3.5]));
+            }
+            values.map(|value| value.to_string())";
+        crate::random::set_seed(7);
+        let first = eval::<Array>(script);
+        crate::random::set_seed(7);
+        let second = eval::<Array>(script);
+        assert_eq!(first.len(), 30);
+        assert_eq!(format!("{:?}", first), format!("{:?}", second));
+        crate::random::set_seed(8);
+        assert_ne!(
+            format!("{:?}", eval::<Array>(script)),
+            format!("{:?}", first)
+        );
+    }
+
+    #[test]
+    fn uncached_runs_do_the_same() {
+        let (_guard, mock) = super::super::backend::install_mock();
+        let script = "mouse_move(10, 20);\nif loop_index == 1 { key_click(Key::a); }\n";
+        let iteration = Iteration { index: 1, count: 2 };
+        run(script, &iteration).unwrap();
+        let cached = mock.trace();
+        mock.clear();
+        run_uncached(script, &iteration).unwrap();
+        assert_eq!(mock.trace(), cached);
+        assert_eq!(cached.len(), 3);
+    }
+
+    #[test]
+    fn failures_name_the_called_function() {
+        let (_guard, mock) = super::super::backend::install_mock();
+        mock.set_refuse(true);
+        for (script, function) in [
+            ("key_press(Key::a)", "key_press"),
+            ("key_click(Key::a)", "key_click"),
+            ("key_click(Key::a, 5)", "key_click_hold"),
+            ("type_keys(\"ab\", 5, 10)", "type_keys"),
+            ("shortcut(Key::a)", "shortcut"),
+            ("select_all()", "select_all"),
+            ("copy()", "copy"),
+            ("save()", "save"),
+            ("button_right_click()", "button_right_click"),
+        ] {
+            let err = ENGINE
+                .with(|engine| engine.eval_with_scope::<Dynamic>(&mut base_scope(), script))
+                .unwrap_err();
+            let failure = match *err {
+                EvalAltResult::ErrorRuntime(value, _) => value.try_cast::<CallFailure>(),
+                _ => None,
+            };
+            assert_eq!(
+                failure.map(|failure| failure.function),
+                Some(function.to_string()),
+                "{}",
+                script
+            );
+        }
+        mock.set_refuse(false);
+    }
+
+    #[test]
+    fn generated_key_names_are_bound() {
+        for (name, key) in KEY_NAMES {
+            assert_eq!(eval::<Key>(&key.script()), key);
+            assert_eq!(eval::<Key>(&format!("key(\"{}\")", name)), key);
+        }
+        let unknown = Key::Unknown(300);
+        assert_eq!(eval::<Key>(&unknown.script()), unknown);
+    }
+
+    #[test]
+    fn generated_button_names_are_bound() {
+        for (_, button) in BUTTON_NAMES {
+            assert_eq!(eval::<Button>(&button.script()), button);
+        }
+        let unknown = Button::Unknown(7);
+        assert_eq!(eval::<Button>(&unknown.script()), unknown);
+    }
+
+    #[test]
+    fn legacy_key_variables_are_bound() {
+        for (name, key) in key_variables() {
+            assert_eq!(eval::<Key>(name), key);
+            assert_eq!(eval::<Key>(&format!("Key::{}", name)), key);
+        }
+    }
+}
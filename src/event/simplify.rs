@@ -0,0 +1,270 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use super::Event;
+use crate::common::{Float, UInt};
+use serde::{Deserialize, Serialize};
+
+///how recorded mouse moves are thinned before a compact script is written, 0 turns a step off,
+///raw scripts are written as recorded and never thinned
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Simplify {
+    ///drop a move closer than this many pixels to the last kept one
+    pub min_distance: Float,
+    ///drop a move fewer millis after the last kept one
+    pub min_step: UInt,
+    ///Ramer-Douglas-Peucker tolerance, the kept path never strays further in pixels from the recorded one
+    pub tolerance: Float,
+}
+
+impl Default for Simplify {
+    fn default() -> Self {
+        Self {
+            min_distance: 1.0,
+            min_step: 0,
+            tolerance: 2.0,
+        }
+    }
+}
+
+impl Simplify {
+    pub fn is_valid(&self) -> bool {
+        self.min_distance >= 0 as Float && self.tolerance >= 0 as Float
+    }
+}
+
+///one recorded move, millis counted from the start of its run
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    x: Float,
+    y: Float,
+    at: UInt,
+}
+
+///thin every run of mouse moves, the first and last move of a run always stay,
+///a kept move waits for all the moves dropped before it so the timeline does not change
+pub fn simplify_moves(events: &[Event], simplify: &Simplify) -> Vec<Event> {
+    let mut simplified = Vec::with_capacity(events.len());
+    let mut index = 0;
+    while index < events.len() {
+        let count = events[index..]
+            .iter()
+            .take_while(|event| matches!(event, Event::MouseMove { .. }))
+            .count();
+        if count < 3 {
+            simplified.push(events[index]);
+            index += 1;
+            continue;
+        }
+        let run = &events[index..index + count];
+        let mut at = 0;
+        let samples: Vec<Sample> = run
+            .iter()
+            .enumerate()
+            .map(|(position, event)| {
+                if position > 0 {
+                    at += event.duration();
+                }
+                let (x, y) = match *event {
+                    Event::MouseMove { x, y, .. } => (x, y),
+                    _ => (0 as Float, 0 as Float),
+                };
+                Sample { x, y, at }
+            })
+            .collect();
+        let kept = douglas_peucker(&thin(&samples, simplify), simplify.tolerance);
+        info!("{} mouse moves simplified to {}", count, kept.len());
+        let mut previous: Option<Sample> = None;
+        for sample in kept {
+            let duration = match previous {
+                Some(previous) => sample.at - previous.at,
+                None => run[0].duration(),
+            };
+            simplified.push(Event::MouseMove {
+                x: sample.x,
+                y: sample.y,
+                elapse: run[0].elapse() + sample.at,
+                duration,
+            });
+            previous = Some(sample);
+        }
+        index += count;
+    }
+    simplified
+}
+
+///drop samples too close in space or time to the last kept one
+fn thin(samples: &[Sample], simplify: &Simplify) -> Vec<Sample> {
+    let mut kept: Vec<Sample> = Vec::with_capacity(samples.len());
+    for (index, sample) in samples.iter().enumerate() {
+        let last = index + 1 == samples.len();
+        let keep = match kept.last() {
+            None => true,
+            Some(previous) => {
+                let distance = (sample.x - previous.x).hypot(sample.y - previous.y);
+                distance >= simplify.min_distance && sample.at - previous.at >= simplify.min_step
+            }
+        };
+        if keep || last {
+            kept.push(*sample);
+        }
+    }
+    kept
+}
+
+fn douglas_peucker(samples: &[Sample], tolerance: Float) -> Vec<Sample> {
+    if samples.len() < 3 || tolerance <= 0 as Float {
+        return samples.to_vec();
+    }
+    let mut keep = vec![false; samples.len()];
+    keep[0] = true;
+    keep[samples.len() - 1] = true;
+    //spans still to check, without recursion so long runs cannot overflow the stack
+    let mut spans = vec![(0, samples.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let mut farthest = (0 as Float, first);
+        for index in first + 1..last {
+            let distance = distance_to_segment(samples[index], samples[first], samples[last]);
+            if distance > farthest.0 {
+                farthest = (distance, index);
+            }
+        }
+        if farthest.0 > tolerance {
+            keep[farthest.1] = true;
+            spans.push((first, farthest.1));
+            spans.push((farthest.1, last));
+        }
+    }
+    samples
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(sample, _)| *sample)
+        .collect()
+}
+
+fn distance_to_segment(point: Sample, start: Sample, end: Sample) -> Float {
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let length = dx * dx + dy * dy;
+    if length == 0 as Float {
+        return (point.x - start.x).hypot(point.y - start.y);
+    }
+    let t = (((point.x - start.x) * dx + (point.y - start.y) * dy) / length).clamp(0.0, 1.0);
+    (point.x - (start.x + dx * t)).hypot(point.y - (start.y + dy * t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{common::timeline, events_to_data, Fidelity, Key};
+
+    fn moved(x: Float, y: Float, duration: UInt) -> Event {
+        Event::MouseMove {
+            x,
+            y,
+            elapse: 0,
+            duration,
+        }
+    }
+
+    ///a key press, a wavy run of moves 8 millis apart and a key release
+    fn recording() -> Vec<Event> {
+        let mut events = vec![Event::KeyPress {
+            key: Key::KeyA,
+            elapse: 0,
+            duration: 0,
+        }];
+        events.push(moved(0.0, 0.0, 30));
+        for step in 1..200 {
+            let x = step as Float * 1.5;
+            let y = (step as Float / 12.0).sin() * 40.0;
+            events.push(moved(x, y, 8));
+        }
+        events.push(Event::KeyRelease {
+            key: Key::KeyA,
+            elapse: 0,
+            duration: 50,
+        });
+        timeline(events)
+    }
+
+    fn sample(event: &Event) -> Sample {
+        match *event {
+            Event::MouseMove { x, y, elapse, .. } => Sample { x, y, at: elapse },
+            _ => panic!("{:?} is not a mouse move", event),
+        }
+    }
+
+    fn moves(events: &[Event]) -> Vec<Sample> {
+        events
+            .iter()
+            .filter(|event| matches!(event, Event::MouseMove { .. }))
+            .map(sample)
+            .collect()
+    }
+
+    #[test]
+    fn dropped_moves_stay_within_tolerance() {
+        let simplify = Simplify {
+            min_distance: 0.0,
+            min_step: 0,
+            tolerance: 2.0,
+        };
+        let events = recording();
+        let simplified = simplify_moves(&events, &simplify);
+        let recorded = moves(&events);
+        let kept = moves(&simplified);
+        assert!(kept.len() < recorded.len() / 4, "{} kept", kept.len());
+        assert_eq!(kept[0].at, recorded[0].at);
+        assert_eq!(kept[kept.len() - 1].at, recorded[recorded.len() - 1].at);
+        for point in recorded {
+            let distance = kept
+                .windows(2)
+                .map(|segment| distance_to_segment(point, segment[0], segment[1]))
+                .fold(Float::MAX, Float::min);
+            assert!(
+                distance <= simplify.tolerance,
+                "{:?} is {} away",
+                point,
+                distance
+            );
+        }
+    }
+
+    #[test]
+    fn timeline_is_unchanged() {
+        let events = recording();
+        let simplified = simplify_moves(&events, &Simplify::default());
+        assert!(simplified.len() < events.len());
+        let total =
+            |events: &[Event]| -> UInt { events.iter().map(|event| event.duration()).sum() };
+        assert_eq!(total(&simplified), total(&events));
+        //a kept move waits longer but happens when it was recorded
+        for event in &simplified {
+            assert!(
+                events
+                    .iter()
+                    .any(|recorded| recorded.set_duration(0) == event.set_duration(0)),
+                "{:?} is not at its recorded elapse",
+                event
+            );
+        }
+    }
+
+    #[test]
+    fn raw_scripts_are_not_thinned() {
+        let events = recording();
+        let thinning = Simplify {
+            min_distance: 50.0,
+            min_step: 100,
+            tolerance: 10.0,
+        };
+        let raw = events_to_data(&events, Fidelity::Raw, &thinning);
+        assert_eq!(raw.content.matches("mouse_move").count(), 200);
+        let compact = events_to_data(&events, Fidelity::Compact, &thinning);
+        assert!(compact.content.matches("mouse_move").count() < 20);
+    }
+}
@@ -0,0 +1,205 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use super::Event;
+use crate::common::Float;
+use lazy_static::lazy_static;
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+///how long a sent event may take to come back through grab
+const ECHO_WINDOW: Duration = Duration::from_millis(500);
+///expected echoes kept at most, older ones are dropped when grab is not running
+const MAX_PENDING: usize = 1024;
+///pixels the OS may move a synthesized pointer event by
+const POINT_TOLERANCE: Float = 1.0;
+
+lazy_static! {
+    ///events sent to the OS that grab has not seen yet
+    static ref PENDING: Mutex<VecDeque<(Event, Instant)>> = Mutex::new(VecDeque::new());
+}
+
+fn pending() -> MutexGuard<'static, VecDeque<(Event, Instant)>> {
+    PENDING.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn prune(pending: &mut VecDeque<(Event, Instant)>) {
+    while let Some((_, sent)) = pending.front() {
+        if sent.elapsed() <= ECHO_WINDOW && pending.len() < MAX_PENDING {
+            break;
+        }
+        pending.pop_front();
+    }
+}
+
+fn same_point(a: (Float, Float), b: (Float, Float)) -> bool {
+    (a.0 - b.0).abs() <= POINT_TOLERANCE && (a.1 - b.1).abs() <= POINT_TOLERANCE
+}
+
+///input device an event comes from, echoes of one device come back in the order they were sent
+fn device(event: &Event) -> u8 {
+    match event {
+        Event::KeyPress { .. } | Event::KeyRelease { .. } => 0,
+        Event::ButtonPress { .. } | Event::ButtonRelease { .. } => 1,
+        Event::MouseMove { .. } | Event::Drag { .. } => 2,
+        Event::Wheel { .. } => 3,
+    }
+}
+
+///whether received can be the echo of sent, timing is ignored
+fn echoes(sent: &Event, received: &Event) -> bool {
+    use Event::*;
+    match (*sent, *received) {
+        (KeyPress { key: a, .. }, KeyPress { key: b, .. }) => a == b,
+        (KeyRelease { key: a, .. }, KeyRelease { key: b, .. }) => a == b,
+        (ButtonPress { button: a, .. }, ButtonPress { button: b, .. }) => a == b,
+        (ButtonRelease { button: a, .. }, ButtonRelease { button: b, .. }) => a == b,
+        //some platforms report a drag as a plain move
+        (
+            MouseMove { x, y, .. } | Drag { x, y, .. },
+            MouseMove { x: x2, y: y2, .. } | Drag { x: x2, y: y2, .. },
+        ) => same_point((x, y), (x2, y2)),
+        (Wheel { x, y, .. }, Wheel { x: x2, y: y2, .. }) => x == x2 && y == y2,
+        _ => false,
+    }
+}
+
+///remember an event about to be sent to the OS
+pub fn expect(event: Event) {
+    let mut pending = pending();
+    prune(&mut pending);
+    pending.push_back((event, Instant::now()));
+}
+
+///the OS refused the event, it will not come back
+pub fn forget(event: Event) {
+    let mut pending = pending();
+    if let Some(index) = pending.iter().rposition(|(sent, _)| *sent == event) {
+        pending.remove(index);
+    }
+}
+
+///true if received is the echo of an event we sent, the expectation is used up;
+///only the oldest event sent from the same device can match, so a physical key
+///is taken for an echo only when it is exactly the one the OS owes us next
+pub fn is_synthetic(received: &Event) -> bool {
+    let mut pending = pending();
+    prune(&mut pending);
+    let next = pending
+        .iter()
+        .position(|(sent, _)| device(sent) == device(received));
+    match next {
+        Some(index) if echoes(&pending[index].0, received) => {
+            pending.remove(index);
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{Button, Key};
+
+    #[test]
+    fn echo_is_matched_once() {
+        let wheel = Event::Wheel {
+            x: 0,
+            y: -3,
+            elapse: 0,
+            duration: 0,
+        };
+        expect(wheel);
+        let other = Event::Wheel {
+            x: 0,
+            y: 3,
+            elapse: 0,
+            duration: 0,
+        };
+        assert!(!is_synthetic(&other));
+        assert!(is_synthetic(&wheel));
+        assert!(!is_synthetic(&wheel));
+    }
+
+    #[test]
+    fn echoes_come_back_in_order() {
+        let key = |key| Event::KeyRelease {
+            key,
+            elapse: 0,
+            duration: 0,
+        };
+        let moved = Event::MouseMove {
+            x: 7.0,
+            y: 7.0,
+            elapse: 0,
+            duration: 0,
+        };
+        expect(key(Key::F7));
+        expect(moved);
+        expect(key(Key::F8));
+        //a physical F8 while F7 is still owed is no echo
+        assert!(!is_synthetic(&key(Key::F8)));
+        //other devices do not wait on the keyboard
+        assert!(is_synthetic(&moved));
+        assert!(is_synthetic(&key(Key::F7)));
+        assert!(is_synthetic(&key(Key::F8)));
+    }
+
+    #[test]
+    fn shapes_must_agree() {
+        let press = Event::ButtonPress {
+            button: Button::Back,
+            x: 10.0,
+            y: 10.0,
+            elapse: 0,
+            duration: 0,
+        };
+        assert!(echoes(&press, &press));
+        assert!(!echoes(
+            &press,
+            &Event::ButtonRelease {
+                button: Button::Back,
+                x: 10.0,
+                y: 10.0,
+                elapse: 0,
+                duration: 0,
+            }
+        ));
+        let moved = Event::MouseMove {
+            x: 100.0,
+            y: 100.0,
+            elapse: 0,
+            duration: 0,
+        };
+        let dragged = Event::Drag {
+            button: Button::Left,
+            x: 100.5,
+            y: 99.5,
+            elapse: 0,
+            duration: 0,
+        };
+        assert!(echoes(&moved, &dragged));
+        assert!(!echoes(
+            &moved,
+            &Event::MouseMove {
+                x: 103.0,
+                y: 100.0,
+                elapse: 0,
+                duration: 0,
+            }
+        ));
+        let key = |key| Event::KeyPress {
+            key,
+            elapse: 0,
+            duration: 0,
+        };
+        assert!(echoes(&key(Key::KeyA), &key(Key::KeyA)));
+        assert!(!echoes(&key(Key::KeyA), &key(Key::KeyB)));
+    }
+}
@@ -0,0 +1,10 @@
+pub mod app;
+mod capture;
+mod common;
+mod database;
+pub mod event;
+mod impls;
+mod random;
+mod settings;
+#[macro_use]
+mod macros;
@@ -1,14 +1,7 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use app::App;
 use eframe::egui;
-mod app;
-mod capture;
-mod common;
-mod event;
-mod impls;
-#[macro_use]
-mod macros;
+use wise_key::app::App;
 fn main() {
     env_logger::init();
     let options = eframe::NativeOptions {
@@ -0,0 +1,125 @@
+//small seedable pseudo random generator (splitmix64),
+//kept in tree so a seed replays the same values on every platform and build
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use crate::common::{Float, Int};
+use lazy_static::lazy_static;
+use std::{
+    sync::{Mutex, MutexGuard, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    ///uniform in [0, 1)
+    pub fn next_float(&mut self) -> Float {
+        (self.next_u64() >> 11) as Float / (1u64 << 53) as Float
+    }
+    ///uniform in [min, max], both ends included
+    pub fn range_int(&mut self, min: Int, max: Int) -> Int {
+        if max <= min {
+            return min;
+        }
+        let span = (max as i64 - min as i64) as u64 + 1;
+        (min as i64 + (self.next_u64() % span) as i64) as Int
+    }
+    ///uniform in [min, max]
+    pub fn range_float(&mut self, min: Float, max: Float) -> Float {
+        if max <= min {
+            return min;
+        }
+        min + self.next_float() * (max - min)
+    }
+}
+
+///seed for a run that was not given one
+pub fn new_seed() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_nanos() as u64,
+        Err(_) => 0,
+    }
+}
+
+lazy_static! {
+    pub static ref RANDOM: Mutex<Random> = Mutex::new(Random::new(new_seed()));
+    static ref JITTER: RwLock<Float> = RwLock::new(0 as Float);
+}
+
+fn random() -> MutexGuard<'static, Random> {
+    RANDOM.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+///restart the sequence, the same seed gives the same values
+pub fn set_seed(seed: u64) {
+    *random() = Random::new(seed);
+}
+
+pub fn random_int(min: Int, max: Int) -> Int {
+    random().range_int(min, max)
+}
+
+pub fn random_float(min: Float, max: Float) -> Float {
+    random().range_float(min, max)
+}
+
+///percent a delay may drift either way, 0 keeps delays exact
+pub fn set_jitter(percent: Float) {
+    *JITTER.write().unwrap() = percent.clamp(0 as Float, 100 as Float);
+}
+
+pub fn jitter_millis(millis: u64) -> u64 {
+    let percent = *JITTER.read().unwrap();
+    if percent == 0 as Float || millis == 0 {
+        return millis;
+    }
+    let factor = 1 as Float + random_float(-percent, percent) / 100 as Float;
+    (millis as Float * factor).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::install_mock;
+
+    fn draw() -> (Vec<Int>, Vec<Float>, Vec<u64>) {
+        let ints = (0..20).map(|_| random_int(-5, 5)).collect();
+        let floats = (0..20).map(|_| random_float(0.5, 1.5)).collect();
+        let delays = (0..20).map(|_| jitter_millis(1000)).collect();
+        (ints, floats, delays)
+    }
+
+    #[test]
+    fn a_seed_replays_the_same_values() {
+        //the generator and jitter are shared with every run
+        let (_guard, _) = install_mock();
+        set_jitter(10.0);
+        set_seed(42);
+        let first = draw();
+        set_seed(42);
+        assert_eq!(draw(), first);
+        set_seed(43);
+        assert_ne!(draw(), first);
+        set_jitter(0.0);
+        assert!(first.0.iter().all(|int| (-5..=5).contains(int)));
+        assert!(first.1.iter().all(|float| (0.5..=1.5).contains(float)));
+        assert!(first.2.iter().all(|delay| (900..=1100).contains(delay)));
+        assert!(first.2.iter().any(|&delay| delay != 1000));
+        assert_eq!(jitter_millis(1000), 1000);
+    }
+}
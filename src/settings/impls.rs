@@ -0,0 +1,33 @@
+use super::SettingsError;
+use crate::database::DatabaseError;
+use std::fmt;
+
+impl From<DatabaseError> for SettingsError {
+    fn from(err: DatabaseError) -> Self {
+        SettingsError::Database(err)
+    }
+}
+
+impl From<serde_json::Error> for SettingsError {
+    fn from(err: serde_json::Error) -> Self {
+        SettingsError::Json(err)
+    }
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Database(err) => write!(f, "{}", err),
+            SettingsError::Json(err) => write!(f, "invalid json: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SettingsError::Database(err) => Some(err),
+            SettingsError::Json(err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,607 @@
+#[allow(unused_imports)]
+use crate::i;
+#[allow(unused_imports)]
+use log::{info, warn};
+
+use crate::{
+    common::{timestamp, Float, Int, UInt, MAX_SPEED, MIN_SPEED},
+    database::{Database, DatabaseError},
+    event::{simulate_predicate, Chord, Data, Fidelity, Iteration, Key, Simplify, SimulateError},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::time::Duration;
+mod impls;
+
+///a chord and whether other applications still receive its key
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Trigger {
+    pub chord: Chord,
+    #[serde(default)]
+    pub passthrough: bool,
+}
+
+impl Trigger {
+    pub fn new(key: Key) -> Self {
+        Self {
+            chord: Chord::new(key),
+            passthrough: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotKey {
+    pub stop: Trigger,
+    pub record: Trigger,
+    pub simulate: Trigger,
+    pub pause: Trigger,
+}
+
+impl Default for HotKey {
+    fn default() -> Self {
+        Self {
+            record: Trigger::new(Key::F10),
+            simulate: Trigger::new(Key::F11),
+            pause: Trigger::new(Key::F9),
+            stop: Trigger::new(Key::F12),
+        }
+    }
+}
+
+impl HotKey {
+    pub fn triggers(&self) -> [Trigger; 4] {
+        [self.record, self.simulate, self.pause, self.stop]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LoopTimes {
+    Unlimited,
+    Limited(Int),
+    ///keep starting loops for this many millis
+    ForDuration(UInt),
+    ///rhai expression checked before every loop, stops once it is true
+    Until(String),
+    ///keep starting loops until this unix timestamp
+    UntilTime(UInt),
+}
+
+impl Default for LoopTimes {
+    fn default() -> Self {
+        LoopTimes::Limited(1)
+    }
+}
+
+impl LoopTimes {
+    ///number of loops if known up front, -1 otherwise
+    pub fn count(&self) -> Int {
+        match *self {
+            LoopTimes::Limited(times) => times,
+            _ => -1,
+        }
+    }
+    ///whether the loop described by iteration should run, elapsed leaves out time spent paused
+    pub fn next(&self, iteration: &Iteration, elapsed: Duration) -> Result<bool, SimulateError> {
+        Ok(match self {
+            LoopTimes::Unlimited => true,
+            LoopTimes::Limited(times) => iteration.index < *times,
+            LoopTimes::ForDuration(millis) => elapsed < Duration::from_millis(*millis as u64),
+            LoopTimes::Until(predicate) => !simulate_predicate(predicate, iteration)?,
+            LoopTimes::UntilTime(at) => timestamp() < *at,
+        })
+    }
+    fn is_valid(&self) -> bool {
+        match self {
+            LoopTimes::Limited(times) => *times > 0,
+            LoopTimes::ForDuration(millis) => *millis > 0,
+            LoopTimes::Until(predicate) => !predicate.trim().is_empty(),
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Speed {
+    ///multiplier of the recorded timing, kept within MIN_SPEED..=MAX_SPEED
+    Multiplier(Float),
+    ///rescale every run to take about this many millis, also kept within MIN_SPEED..=MAX_SPEED
+    FitTo(UInt),
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed::Multiplier(1 as Float)
+    }
+}
+
+impl Speed {
+    pub fn factor(&self, data: &Data) -> Float {
+        match *self {
+            Speed::Multiplier(factor) => factor.clamp(MIN_SPEED, MAX_SPEED),
+            Speed::FitTo(millis) => match data.timeline_millis() {
+                Some(total) if total > 0 && millis > 0 => {
+                    let factor = total as Float / millis as Float;
+                    let clamped = factor.clamp(MIN_SPEED, MAX_SPEED);
+                    if clamped != factor {
+                        warn!(
+                            "{} takes {}ms, {}ms is out of reach, running at {}x",
+                            data.name, total, millis, clamped
+                        );
+                    }
+                    clamped
+                }
+                _ => 1 as Float,
+            },
+        }
+    }
+    fn is_valid(&self) -> bool {
+        match *self {
+            Speed::Multiplier(factor) => (MIN_SPEED..=MAX_SPEED).contains(&factor),
+            Speed::FitTo(millis) => millis > 0,
+        }
+    }
+}
+
+///a key that runs a macro of the library, by name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub trigger: Trigger,
+    pub name: String,
+    #[serde(default)]
+    pub loop_times: LoopTimes,
+    #[serde(default)]
+    pub speed: Speed,
+}
+
+///randomness of a run
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Variation {
+    ///fixed seed replays a run exactly, None picks a new one every run
+    pub seed: Option<u64>,
+    ///percent recorded delays may drift either way
+    pub jitter: Float,
+}
+
+///ways to stop a run besides the stop hotkey, 0 turns a limit off
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Failsafe {
+    ///stop when the mouse is moved into a corner of the screen
+    pub corner: bool,
+    ///pixels from the corner that still count
+    pub corner_margin: UInt,
+    ///stop after this many quick presses of Escape
+    pub escape_presses: UInt,
+    ///seconds a run may take at most
+    pub max_runtime: UInt,
+    ///synthesized events a second may hold at most
+    pub max_events_per_second: UInt,
+}
+
+impl Default for Failsafe {
+    fn default() -> Self {
+        Self {
+            corner: true,
+            corner_margin: 2,
+            escape_presses: 3,
+            max_runtime: 0,
+            max_events_per_second: 0,
+        }
+    }
+}
+
+///everything the user may change, each field is a row of table `settings`,
+///key is the field name and value its json, e.g. `loop_interval` = `250`,
+///`loop_times` = `{"Limited":3}` or `hotkey` = `{"stop":{"chord":"Ctrl+F12"}}`,
+///missing fields of a row and missing rows keep their defaults
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub hotkey: HotKey,
+    pub bindings: Vec<Binding>,
+    pub loop_times: LoopTimes,
+    ///millis to wait between two loops
+    pub loop_interval: UInt,
+    pub speed: Speed,
+    pub variation: Variation,
+    pub failsafe: Failsafe,
+    ///drop physical input while a simulation runs, except the stop and pause hotkeys
+    pub input_lock: bool,
+    ///also give scripts every key as a global variable, as scripts recorded before Key::name expect
+    pub legacy_keys: bool,
+    ///leave mouse moves out of recordings
+    pub mouse_filter: bool,
+    ///whether recordings fold clicks, drags and typing into single calls
+    pub fidelity: Fidelity,
+    ///thinning of mouse moves, only compact recordings are thinned, raw ones keep every move
+    pub simplify: Simplify,
+    ///part of the application identifier of the window to capture
+    pub capture_target: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            hotkey: HotKey::default(),
+            bindings: Vec::new(),
+            loop_times: LoopTimes::default(),
+            loop_interval: 0,
+            speed: Speed::default(),
+            variation: Variation::default(),
+            failsafe: Failsafe::default(),
+            input_lock: false,
+            legacy_keys: true,
+            mouse_filter: false,
+            fidelity: Fidelity::default(),
+            simplify: Simplify::default(),
+            capture_target: "chrome".to_string(),
+        }
+    }
+}
+
+impl Settings {
+    ///read settings from the database, rows that are unknown or invalid fall back to defaults,
+    ///first launch stores the defaults so they can be edited, what was reset is written back
+    pub fn load(database: &Database) -> Result<Self, SettingsError> {
+        let rows = database.settings()?;
+        if rows.is_empty() {
+            let settings = Self::default();
+            settings.save(database)?;
+            return Ok(settings);
+        }
+        let (settings, problems) = Self::merge(rows)?;
+        for problem in &problems {
+            warn!("{}", problem);
+        }
+        if !problems.is_empty() {
+            settings.save(database)?;
+        }
+        if settings.fidelity == Fidelity::Raw && settings.simplify != Simplify::default() {
+            warn!("simplify is set but raw recordings keep every mouse move");
+        }
+        Ok(settings)
+    }
+
+    ///settings edited as one json object, every field as its row would hold it,
+    ///returns them validated along with what was left out or reset
+    pub fn parse(text: &str) -> Result<(Self, Vec<String>), SettingsError> {
+        let fields: Map<String, Value> = serde_json::from_str(text)?;
+        let rows = fields
+            .into_iter()
+            .map(|(key, value)| (key, value.to_string()))
+            .collect();
+        Self::merge(rows)
+    }
+
+    ///defaults overridden by every (field, json) row that parses, then validated
+    fn merge(rows: Vec<(String, String)>) -> Result<(Self, Vec<String>), SettingsError> {
+        let mut problems = Vec::new();
+        let mut merged = serde_json::to_value(Self::default())?;
+        for (key, value) in rows {
+            if merged.get(&key).is_none() {
+                problems.push(format!("unknown setting {}", key));
+                continue;
+            }
+            let mut candidate = merged.clone();
+            candidate[key.as_str()] = match serde_json::from_str::<Value>(&value) {
+                Ok(value) => value,
+                Err(err) => {
+                    problems.push(format!("setting {} is not json: {}", key, err));
+                    continue;
+                }
+            };
+            match serde_json::from_value::<Self>(candidate.clone()) {
+                Ok(_) => merged = candidate,
+                Err(err) => problems.push(format!("setting {} is invalid: {}", key, err)),
+            }
+        }
+        let mut settings: Self = serde_json::from_value(merged)?;
+        for problem in settings.validate() {
+            problems.push(format!("setting reset to default, {}", problem));
+        }
+        Ok((settings, problems))
+    }
+
+    pub fn save(&self, database: &Database) -> Result<(), SettingsError> {
+        if let Value::Object(fields) = serde_json::to_value(self)? {
+            for (key, value) in fields {
+                database.set_setting(&key, &value.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    ///replace values that parse but make no sense, returns what was replaced
+    pub fn validate(&mut self) -> Vec<String> {
+        let default = Self::default();
+        let mut problems = Vec::new();
+        let triggers = self.hotkey.triggers();
+        let clash = triggers.iter().enumerate().any(|(index, trigger)| {
+            triggers[index + 1..]
+                .iter()
+                .any(|other| other.chord == trigger.chord)
+        });
+        if clash {
+            problems.push("hotkey uses the same chord twice".to_string());
+            self.hotkey = default.hotkey;
+        }
+        let mut bindings: Vec<Binding> = Vec::new();
+        for binding in self.bindings.drain(..) {
+            let taken = self
+                .hotkey
+                .triggers()
+                .iter()
+                .chain(bindings.iter().map(|binding| &binding.trigger))
+                .any(|trigger| trigger.chord == binding.trigger.chord);
+            if binding.name.trim().is_empty() {
+                problems.push(format!(
+                    "binding {} has no macro name",
+                    binding.trigger.chord
+                ));
+            } else if taken {
+                problems.push(format!(
+                    "binding {} is already in use",
+                    binding.trigger.chord
+                ));
+            } else if !binding.loop_times.is_valid() || !binding.speed.is_valid() {
+                problems.push(format!("binding {} loop or speed", binding.trigger.chord));
+            } else {
+                bindings.push(binding);
+            }
+        }
+        self.bindings = bindings;
+        if !self.loop_times.is_valid() {
+            problems.push("loop_times".to_string());
+            self.loop_times = default.loop_times;
+        }
+        if !self.speed.is_valid() {
+            problems.push("speed".to_string());
+            self.speed = default.speed;
+        }
+        if !(0 as Float..=100 as Float).contains(&self.variation.jitter) {
+            problems.push("variation jitter".to_string());
+            self.variation.jitter = default.variation.jitter;
+        }
+        if !self.simplify.is_valid() {
+            problems.push("simplify distances".to_string());
+            self.simplify = default.simplify;
+        }
+        if self.failsafe.escape_presses == 1 {
+            problems
+                .push("failsafe escape_presses, a single Escape is too easy to hit".to_string());
+            self.failsafe.escape_presses = default.failsafe.escape_presses;
+        }
+        if self.capture_target.trim().is_empty() {
+            problems.push("capture_target".to_string());
+            self.capture_target = default.capture_target;
+        }
+        problems
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Database(DatabaseError),
+    Json(serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///a generated script whose last event is 1000 millis in
+    fn second_long() -> Data {
+        Data::new(
+            "second".to_string(),
+            "key_press(Key::a);\ndelay(1000);\nkey_release(Key::a);\n".to_string(),
+        )
+    }
+
+    #[test]
+    fn fit_to_scales_the_timeline() {
+        assert_eq!(Speed::FitTo(500).factor(&second_long()), 2.0);
+        assert_eq!(Speed::FitTo(2000).factor(&second_long()), 0.5);
+    }
+
+    #[test]
+    fn fit_to_is_clamped() {
+        assert_eq!(Speed::FitTo(10).factor(&second_long()), MAX_SPEED);
+        assert_eq!(Speed::FitTo(100_000).factor(&second_long()), MIN_SPEED);
+        assert_eq!(Speed::Multiplier(100.0).factor(&second_long()), MAX_SPEED);
+    }
+
+    fn rows(database: &Database) -> Vec<(String, String)> {
+        database.settings().unwrap()
+    }
+
+    fn row(database: &Database, key: &str) -> String {
+        rows(database)
+            .into_iter()
+            .find(|(row, _)| row == key)
+            .map(|(_, value)| value)
+            .unwrap()
+    }
+
+    #[test]
+    fn first_load_stores_defaults() {
+        let database = Database::memory();
+        assert_eq!(Settings::load(&database).unwrap(), Settings::default());
+        let fields = match serde_json::to_value(Settings::default()).unwrap() {
+            Value::Object(fields) => fields.len(),
+            _ => 0,
+        };
+        assert_eq!(rows(&database).len(), fields);
+        assert_eq!(row(&database, "loop_interval"), "0");
+    }
+
+    #[test]
+    fn partial_rows_are_merged() {
+        let database = Database::memory();
+        database.set_setting("loop_interval", "250").unwrap();
+        database
+            .set_setting("hotkey", r#"{"stop":{"chord":"Ctrl+F12"}}"#)
+            .unwrap();
+        let settings = Settings::load(&database).unwrap();
+        assert_eq!(settings.loop_interval, 250);
+        assert_eq!(
+            settings.hotkey.stop.chord,
+            "Ctrl+F12".parse::<Chord>().unwrap()
+        );
+        assert_eq!(settings.hotkey.record, HotKey::default().record);
+        assert_eq!(settings.speed, Speed::default());
+    }
+
+    #[test]
+    fn invalid_rows_fall_back_and_are_written_back() {
+        let database = Database::memory();
+        database.set_setting("loop_interval", r#""soon""#).unwrap();
+        database.set_setting("speed", "{").unwrap();
+        database
+            .set_setting("loop_times", r#"{"Limited":0}"#)
+            .unwrap();
+        database.set_setting("input_lock", "true").unwrap();
+        database.set_setting("no_such_setting", "1").unwrap();
+        let settings = Settings::load(&database).unwrap();
+        assert_eq!(settings.loop_interval, 0);
+        assert_eq!(settings.speed, Speed::default());
+        assert_eq!(settings.loop_times, LoopTimes::default());
+        assert!(settings.input_lock);
+        assert_eq!(row(&database, "loop_interval"), "0");
+        assert_eq!(row(&database, "loop_times"), r#"{"Limited":1}"#);
+        assert_eq!(row(&database, "input_lock"), "true");
+    }
+
+    #[test]
+    fn clashing_bindings_are_dropped() {
+        let binding = |chord: &str, name: &str| Binding {
+            trigger: Trigger {
+                chord: chord.parse().unwrap(),
+                passthrough: false,
+            },
+            name: name.to_string(),
+            loop_times: LoopTimes::default(),
+            speed: Speed::default(),
+        };
+        let bindings = vec![
+            binding("F1", "first"),
+            binding("F1", "again"),
+            binding("F10", "record"),
+            binding("F2", " "),
+            binding("Ctrl+F1", "second"),
+        ];
+        let database = Database::memory();
+        database
+            .set_setting("bindings", &serde_json::to_string(&bindings).unwrap())
+            .unwrap();
+        let settings = Settings::load(&database).unwrap();
+        let names: Vec<&str> = settings.bindings.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, vec!["first", "second"]);
+    }
+
+    #[test]
+    fn reload_sees_new_rows() {
+        let database = Database::memory();
+        assert_eq!(Settings::load(&database).unwrap().loop_interval, 0);
+        database.set_setting("loop_interval", "40").unwrap();
+        assert_eq!(Settings::load(&database).unwrap().loop_interval, 40);
+    }
+
+    #[test]
+    fn parse_reports_what_it_left_out() {
+        let (settings, problems) =
+            Settings::parse(r#"{"loop_interval": 40, "speed": {"Multiplier": 0}, "colour": 1}"#)
+                .unwrap();
+        assert_eq!(settings.loop_interval, 40);
+        assert_eq!(settings.speed, Speed::default());
+        assert_eq!(
+            problems,
+            vec![
+                "unknown setting colour".to_string(),
+                "setting reset to default, speed".to_string(),
+            ]
+        );
+        assert!(Settings::parse("[1]").is_err());
+        let (round_trip, problems) =
+            Settings::parse(&serde_json::to_string(&Settings::default()).unwrap()).unwrap();
+        assert_eq!(round_trip, Settings::default());
+        assert!(problems.is_empty());
+    }
+
+    fn iteration(index: Int) -> Iteration {
+        Iteration { index, count: -1 }
+    }
+
+    #[test]
+    fn limited_runs_that_many_loops() {
+        let times = LoopTimes::Limited(2);
+        assert_eq!(times.count(), 2);
+        assert!(times.next(&iteration(0), Duration::ZERO).unwrap());
+        assert!(times.next(&iteration(1), Duration::ZERO).unwrap());
+        assert!(!times.next(&iteration(2), Duration::ZERO).unwrap());
+        assert!(LoopTimes::Unlimited
+            .next(&iteration(1_000_000), Duration::MAX)
+            .unwrap());
+        assert_eq!(LoopTimes::Unlimited.count(), -1);
+    }
+
+    #[test]
+    fn for_duration_counts_the_time_given() {
+        let times = LoopTimes::ForDuration(100);
+        assert_eq!(times.count(), -1);
+        assert!(times
+            .next(&iteration(50), Duration::from_millis(99))
+            .unwrap());
+        assert!(!times
+            .next(&iteration(0), Duration::from_millis(100))
+            .unwrap());
+    }
+
+    #[test]
+    fn until_stops_once_true() {
+        let times = LoopTimes::Until("loop_index >= 2".to_string());
+        assert!(times.next(&iteration(1), Duration::ZERO).unwrap());
+        assert!(!times.next(&iteration(2), Duration::ZERO).unwrap());
+    }
+
+    #[test]
+    fn until_error_is_returned() {
+        let times = LoopTimes::Until("no_such_variable > 1".to_string());
+        assert!(times.next(&iteration(0), Duration::ZERO).is_err());
+        let times = LoopTimes::Until("loop_index +".to_string());
+        assert!(times.next(&iteration(0), Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn until_time_compares_the_clock() {
+        let now = timestamp();
+        let ahead = LoopTimes::UntilTime(now + 60);
+        assert!(ahead.next(&iteration(0), Duration::ZERO).unwrap());
+        let behind = LoopTimes::UntilTime(now - 1);
+        assert!(!behind.next(&iteration(0), Duration::ZERO).unwrap());
+    }
+
+    #[test]
+    fn invalid_loop_times_are_reset() {
+        for times in [
+            LoopTimes::Limited(0),
+            LoopTimes::ForDuration(0),
+            LoopTimes::Until(" ".to_string()),
+        ] {
+            let mut settings = Settings {
+                loop_times: times,
+                ..Settings::default()
+            };
+            assert_eq!(settings.validate(), vec!["loop_times".to_string()]);
+            assert_eq!(settings.loop_times, LoopTimes::default());
+        }
+    }
+
+    #[test]
+    fn fit_to_without_timeline_keeps_speed() {
+        let handwritten = Data::new("loop".to_string(), "for i in 0..3 {}".to_string());
+        assert_eq!(Speed::FitTo(500).factor(&handwritten), 1.0);
+    }
+}